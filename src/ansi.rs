@@ -0,0 +1,87 @@
+use crate::face::GameCubeFace;
+use crate::game::Game;
+use crate::player::{win_position, GamePlayerIndex};
+use crate::position::GameCubePosition;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const WIN_SQUARE: &str = "\x1b[43m";
+
+/// Width of a board cell in characters, without borders.
+const CELL_WIDTH: usize = 7;
+
+fn player_colour(player: GamePlayerIndex) -> &'static str {
+    match player {
+        1 => "\x1b[34m",
+        2 => "\x1b[31m",
+        _ => unreachable!("Game Players only have indexes 1 and 2."),
+    }
+}
+
+fn border(left: char, middle: char, right: char) -> String {
+    let segment = "─".repeat(CELL_WIDTH);
+    let inner = vec![segment; 7].join(&middle.to_string());
+    format!("  {}{}{}", left, inner, right)
+}
+
+impl Game {
+    /// Board for ANSI terminals: cubes coloured by player, win squares
+    /// highlighted. Each cube shows its up face in bold, followed by its
+    /// front (↑, towards row 1) and right (→, towards column g) faces.
+    pub fn format_board_ansi(&self) -> String {
+        let mut lines = vec![];
+        let header: Vec<String> = ('a'..='g')
+            .map(|col| format!("{:^width$}", col, width = CELL_WIDTH))
+            .collect();
+        lines.push(format!("   {}", header.join(" ")));
+        lines.push(border('┌', '┬', '┐'));
+        for y in 1..=7 {
+            let cells: Vec<String> = (1..=7)
+                .map(|x| self.format_cell_ansi(GameCubePosition { x, y }))
+                .collect();
+            lines.push(format!("{} │{}│", y, cells.join("│")));
+            if y < 7 {
+                lines.push(border('├', '┼', '┤'));
+            }
+        }
+        lines.push(border('└', '┴', '┘'));
+        lines.push(format!(
+            "  {}1{} / {}2{}: up face, ↑front, →right; {}  {} win squares",
+            player_colour(1),
+            RESET,
+            player_colour(2),
+            RESET,
+            WIN_SQUARE,
+            RESET
+        ));
+        lines.join("\n")
+    }
+
+    fn format_cell_ansi(&self, pos: GameCubePosition) -> String {
+        let background = if pos == win_position(1) || pos == win_position(2) {
+            WIN_SQUARE
+        } else {
+            ""
+        };
+        match self.get_cube_at(pos) {
+            Some(cube) => {
+                let up: GameCubeFace = cube.direction.up.into();
+                let front: GameCubeFace = cube.direction.front.into();
+                let right: GameCubeFace = cube.direction.right.into();
+                format!(
+                    "{bg}{colour} {BOLD}{up}{RESET}{bg}{colour}{DIM} ↑{front}→{right}{RESET}",
+                    bg = background,
+                    colour = player_colour(cube.player),
+                    up = up.repr_char(),
+                    front = front.repr_char(),
+                    right = right.repr_char(),
+                )
+            }
+            None if !background.is_empty() => {
+                format!("{}{:^width$}{}", background, "★", RESET, width = CELL_WIDTH)
+            }
+            None => " ".repeat(CELL_WIDTH),
+        }
+    }
+}
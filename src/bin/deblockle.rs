@@ -0,0 +1,323 @@
+//! Play Deblockle locally in the terminal, without a deployed contract.
+//!
+//! Usage: `deblockle [--cubes N] [--bot 1|2]`
+//!
+//! Moves are entered in algebraic form, e.g. `c5-c4` for a Roll and `c4xf4`
+//! for a Hop. Type `help` for the list of commands.
+//!
+//! The board is drawn with ANSI colours when printing to a terminal, unless
+//! `NO_COLOR` is set.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::{env, fs, process};
+
+use near_deblockle::game::{Game, GamePhase};
+use near_deblockle::notation::{GameMove, MoveKind};
+use near_deblockle::player::{win_position, GamePlayerIndex};
+use near_deblockle::position::GameCubePosition;
+use near_deblockle::svg::SvgOptions;
+
+const HELP: &str = "\
+Commands:
+  <from>-<to>     roll a cube, e.g. c5-c4
+  <from>x<to>     hop with the rolled cube, e.g. c4xf4
+  pass            pass the rest of the turn
+  moves           list legal moves for the active player
+  cube <square>   show the unfolded layout of the cube at <square>
+  board           print the board
+  undo            take back the last move
+  bot <1|2|off>   let the computer play for a player
+  new [cubes]     start a new game (1..4 cubes per player, default 4)
+  save <file>     save the moves played so far
+  svg <file>      write the board as an SVG picture
+  load <file>     replay moves from a file
+  help            show this help
+  quit            leave";
+
+struct Session {
+    num_cubes: Option<usize>,
+    game: Game,
+    history: Vec<Game>,
+    moves: Vec<String>,
+    bot: Option<GamePlayerIndex>,
+}
+
+impl Session {
+    fn new(num_cubes: Option<usize>, bot: Option<GamePlayerIndex>) -> Self {
+        Self {
+            num_cubes,
+            game: Game::game_setup(num_cubes),
+            history: vec![],
+            moves: vec![],
+            bot,
+        }
+    }
+
+    fn print_board(&self) {
+        let status = match self.game.phase {
+            GamePhase::End => "finished",
+            _ => "active",
+        };
+        println!(
+            "Status: {}, Player: {}, Phase: {:?}\n",
+            status, self.game.active_player, self.game.phase
+        );
+        if io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() {
+            println!("{}\n", self.game.format_board_ansi());
+        } else {
+            println!("{}\n", self.game.format_board());
+        }
+    }
+
+    /// Apply a move given in notation. Illegal moves leave the game untouched.
+    fn play(&mut self, mv: &str) -> Result<String, String> {
+        if mv == "pass" {
+            let mut game = self.game.clone();
+            let report = game
+                .pass_turn()
+                .ok_or("Game is finished, no moves allowed")?;
+            self.commit(game, mv.to_string());
+            return Ok(report);
+        }
+
+        let game_move = GameMove::parse(mv).ok_or(format!("Cannot parse move '{}'", mv))?;
+        if MoveKind::for_phase(self.game.phase) != Some(game_move.kind) {
+            return Err(format!(
+                "'{}' is a {:?}, but it is {:?} phase",
+                mv, game_move.kind, self.game.phase
+            ));
+        }
+        let (from, to) = (game_move.from, game_move.to);
+        let mut game = self.game.clone();
+        let report = game.try_make_move(from, to);
+        if self.game.legal_targets(from).contains(&to) {
+            self.commit(game, game_move.to_string());
+            Ok(report)
+        } else {
+            Err(report)
+        }
+    }
+
+    fn commit(&mut self, game: Game, mv: String) {
+        self.history.push(std::mem::replace(&mut self.game, game));
+        self.moves.push(mv);
+    }
+
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(game) => {
+                self.game = game;
+                self.moves.pop();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bot_to_move(&self) -> bool {
+        self.bot == Some(self.game.active_player) && self.game.phase != GamePhase::End
+    }
+
+    /// Let the bot play until it is the human's turn again.
+    fn run_bot(&mut self) {
+        while self.bot_to_move() {
+            let mv = choose_bot_move(&self.game);
+            match self.play(&mv) {
+                Ok(report) => println!("bot: {}\n{}", mv, report),
+                Err(err) => {
+                    println!("bot failed to move: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn save(&self, path: &str) -> io::Result<()> {
+        let mut content = format!("cubes {}\n", self.num_cubes.unwrap_or(4));
+        for mv in &self.moves {
+            content.push_str(mv);
+            content.push('\n');
+        }
+        fs::write(path, content)
+    }
+
+    fn load(path: &str, bot: Option<GamePlayerIndex>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut num_cubes = None;
+        let mut moves = vec![];
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix("cubes ") {
+                Some(n) => num_cubes = Some(parse_cubes(n.trim()).ok_or("bad cubes line")?),
+                None => moves.push(line.to_string()),
+            }
+        }
+        let mut session = Self::new(num_cubes, bot);
+        for (i, mv) in moves.iter().enumerate() {
+            session
+                .play(mv)
+                .map_err(|err| format!("move {} '{}': {}", i + 1, mv, err))?;
+        }
+        Ok(session)
+    }
+}
+
+fn format_move(game: &Game, from: GameCubePosition, to: GameCubePosition) -> String {
+    let kind = MoveKind::for_phase(game.phase).unwrap_or(MoveKind::Roll);
+    GameMove { from, to, kind }.to_string()
+}
+
+/// Greedy bot: score if possible, otherwise bring a cube closest to its
+/// win square. Passes when nothing is legal.
+fn choose_bot_move(game: &Game) -> String {
+    let target = win_position(game.active_player);
+    let distance = |p: GameCubePosition| (p.x - target.x).abs() + (p.y - target.y).abs();
+    game.legal_moves()
+        .into_iter()
+        .min_by_key(|(from, to)| (distance(*to), -distance(*from)))
+        .map(|(from, to)| format_move(game, from, to))
+        .unwrap_or_else(|| "pass".to_string())
+}
+
+fn parse_args() -> Result<(Option<usize>, Option<GamePlayerIndex>), String> {
+    let mut num_cubes = None;
+    let mut bot = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", arg))?;
+        match arg.as_str() {
+            "--cubes" => num_cubes = Some(parse_cubes(&value).ok_or("--cubes takes 1..4")?),
+            "--bot" => bot = Some(parse_player(&value).ok_or("--bot takes 1 or 2")?),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok((num_cubes, bot))
+}
+
+/// Number of cubes per player, as `GameParams::validate` accepts it.
+fn parse_cubes(s: &str) -> Option<usize> {
+    s.parse().ok().filter(|n| (1..=4).contains(n))
+}
+
+fn parse_player(s: &str) -> Option<GamePlayerIndex> {
+    match s {
+        "1" => Some(1),
+        "2" => Some(2),
+        _ => None,
+    }
+}
+
+fn main() {
+    let (num_cubes, bot) = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\nusage: deblockle [--cubes N] [--bot 1|2]", err);
+        process::exit(2);
+    });
+
+    let mut session = Session::new(num_cubes, bot);
+    println!("Deblockle. Type `help` for commands.\n");
+    session.run_bot();
+    session.print_board();
+
+    let stdin = io::stdin();
+    loop {
+        print!("player {}> ", session.game.active_player);
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let arg = words.next();
+
+        match (command, arg) {
+            ("quit" | "exit", _) => break,
+            ("help", _) => println!("{}", HELP),
+            ("board", _) => session.print_board(),
+            ("moves", _) => {
+                let moves: Vec<String> = session
+                    .game
+                    .legal_moves()
+                    .into_iter()
+                    .map(|(from, to)| format_move(&session.game, from, to))
+                    .collect();
+                if moves.is_empty() {
+                    println!("no legal moves, you may only pass");
+                } else {
+                    println!("{}", moves.join(" "));
+                }
+            }
+            ("cube", Some(square)) => {
+                match GameCubePosition::from_square(square)
+                    .and_then(|pos| session.game.get_cube_at(pos))
+                {
+                    Some(cube) => println!(
+                        "player {} cube at {}:\n{}",
+                        cube.player,
+                        square,
+                        cube.direction.format_layout()
+                    ),
+                    None => println!("no cube at '{}'", square),
+                }
+            }
+            ("undo", _) => {
+                if !session.undo() {
+                    println!("nothing to undo");
+                }
+                while session.bot_to_move() && session.undo() {}
+                session.print_board();
+            }
+            ("bot", Some(player)) => {
+                session.bot = parse_player(player);
+                session.run_bot();
+                session.print_board();
+            }
+            ("new", arg) => match arg.map(parse_cubes) {
+                Some(None) => println!("a game takes 1..4 cubes per player"),
+                num_cubes => {
+                    let num_cubes = num_cubes.flatten().or(session.num_cubes);
+                    session = Session::new(num_cubes, session.bot);
+                    session.run_bot();
+                    session.print_board();
+                }
+            },
+            ("save", Some(path)) => match session.save(path) {
+                Ok(()) => println!("saved {} moves to {}", session.moves.len(), path),
+                Err(err) => println!("cannot save: {}", err),
+            },
+            ("svg", Some(path)) => {
+                let options = SvgOptions {
+                    last_move: session.moves.last().and_then(|mv| GameMove::parse(mv)),
+                    legal_moves: true,
+                };
+                match fs::write(path, session.game.format_svg(&options)) {
+                    Ok(()) => println!("board written to {}", path),
+                    Err(err) => println!("cannot write svg: {}", err),
+                }
+            }
+            ("load", Some(path)) => match Session::load(path, session.bot) {
+                Ok(loaded) => {
+                    session = loaded;
+                    session.run_bot();
+                    session.print_board();
+                }
+                Err(err) => println!("cannot load: {}", err),
+            },
+            (mv, None) => match session.play(mv) {
+                Ok(report) => {
+                    println!("{}", report);
+                    session.run_bot();
+                    session.print_board();
+                }
+                Err(report) => println!("{}", report),
+            },
+            _ => println!("unknown command, type `help`"),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use near_sdk::serde::Serialize;
+
+use crate::face::{opposite_face, GameCubeFace};
+use crate::game::{Game, GameCube, GamePhase};
+use crate::player::{win_position, GamePlayerIndex};
+use crate::position::GameCubePosition;
+
+/// The board laid out for frontends: `rows[y - 1][x - 1]`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardView {
+    pub phase: GamePhase,
+    pub active_player: GamePlayerIndex,
+    pub rows: Vec<Vec<CellView>>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CellView {
+    pub position: GameCubePosition,
+    /// Algebraic name of the square, e.g. "d2".
+    pub square: String,
+    /// Index of the player whose win square this is.
+    pub win_square_of: Option<GamePlayerIndex>,
+    pub cube: Option<CubeView>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CubeView {
+    pub player: GamePlayerIndex,
+    pub faces: CubeFaces,
+    /// The cube may be moved by the active player in this phase.
+    pub selectable: bool,
+    /// Squares the cube may legally move to.
+    pub targets: Vec<GameCubePosition>,
+}
+
+/// Face indexes on all six sides of a cube.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CubeFaces {
+    pub up: i8,
+    pub down: i8,
+    pub front: i8,
+    pub back: i8,
+    pub left: i8,
+    pub right: i8,
+}
+
+impl From<&GameCube> for CubeFaces {
+    fn from(cube: &GameCube) -> Self {
+        let opposite = |face: i8| opposite_face(GameCubeFace::from(face)) as i8;
+        let direction = cube.direction;
+        Self {
+            up: direction.up,
+            down: opposite(direction.up),
+            front: direction.front,
+            back: opposite(direction.front),
+            left: opposite(direction.right),
+            right: direction.right,
+        }
+    }
+}
+
+impl Game {
+    pub fn board_view(&self) -> BoardView {
+        let rows = (1..=7)
+            .map(|y| {
+                (1..=7)
+                    .map(|x| self.cell_view(GameCubePosition { x, y }))
+                    .collect()
+            })
+            .collect();
+        BoardView {
+            phase: self.phase,
+            active_player: self.active_player,
+            rows,
+        }
+    }
+
+    fn cell_view(&self, position: GameCubePosition) -> CellView {
+        let win_square_of = [1, 2]
+            .into_iter()
+            .find(|player| win_position(*player) == position);
+        let cube = self.get_cube_at(position).map(|cube| {
+            let targets = self.legal_targets(position);
+            CubeView {
+                player: cube.player,
+                faces: CubeFaces::from(&cube),
+                selectable: !targets.is_empty(),
+                targets,
+            }
+        });
+        CellView {
+            position,
+            square: position.to_string(),
+            win_square_of,
+            cube,
+        }
+    }
+}
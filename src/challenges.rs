@@ -0,0 +1,283 @@
+//! Open challenges: players post the game they want with their stake in
+//! escrow, and whoever fits the challenge accepts it with the same stake.
+//! The contract plays one game at a time, so accepted challenges wait in
+//! line, oldest first, and each becomes the game once the contract is free:
+//! the challenger as its first player, the opponent as the second.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Promise, PromiseOrValue};
+
+use crate::currency::Currency;
+use crate::events::Event;
+use crate::interface::GameParams;
+use crate::profiles::INITIAL_RATING;
+use crate::{Contract, ContractExt};
+
+pub type ChallengeId = u64;
+
+const MAX_CHALLENGES_LIMIT: u64 = 100;
+
+/// What a challenger asks for: the game, and who may accept it.
+#[derive(Clone, Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChallengeParams {
+    #[serde(flatten)]
+    pub game: GameParams,
+    /// Lowest rating of an opponent, see `profile`.
+    pub min_rating: Option<u32>,
+    /// Highest rating of an opponent.
+    pub max_rating: Option<u32>,
+}
+
+#[derive(Clone, Debug, BorshDeserialize, BorshSerialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Challenge {
+    pub challenge_id: ChallengeId,
+    pub challenger: AccountId,
+    pub currency: Currency,
+    /// Kept by the contract until the challenge is accepted or cancelled.
+    pub stake: U128,
+    pub params: ChallengeParams,
+}
+
+/// A challenge accepted by `opponent`, waiting for the game to be free.
+#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptedChallenge {
+    #[serde(flatten)]
+    pub challenge: Challenge,
+    /// Staked as much as the challenger, kept by the contract too.
+    pub opponent: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn challenge(&self, challenge_id: ChallengeId) -> Option<Challenge> {
+        self.challenges.get(&challenge_id)
+    }
+
+    /// Open challenges, oldest first: `limit` of them (10 by default, at
+    /// most 100) starting at `from_index`.
+    pub fn challenges(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Challenge> {
+        self.challenges
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(10).min(MAX_CHALLENGES_LIMIT) as usize)
+            .collect()
+    }
+
+    /// Accepted challenges waiting to be played, next first: `limit` of
+    /// them (10 by default, at most 100) starting at `from_index`.
+    pub fn accepted_challenges(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<AcceptedChallenge> {
+        self.accepted
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(10).min(MAX_CHALLENGES_LIMIT) as usize)
+            .map(|(_, accepted)| accepted)
+            .collect()
+    }
+
+    /// Withdraw the challenge `challenge_id` and refund the stake. Once it is
+    /// accepted, either player may withdraw it until it is played, and both
+    /// are refunded.
+    pub fn cancel_challenge(&mut self, challenge_id: ChallengeId) -> Promise {
+        if let Some(accepted) = self.accepted.get(&challenge_id) {
+            return self.cancel_accepted_challenge(accepted);
+        }
+        let challenge = self
+            .challenges
+            .get(&challenge_id)
+            .expect("no such challenge");
+        require!(
+            challenge.challenger == env::predecessor_account_id(),
+            "only the challenger can cancel the challenge"
+        );
+        self.challenges.remove(&challenge_id);
+        self.release_game_storage(&challenge.challenger);
+        Event::ChallengeCancelled {
+            challenge_id,
+            account_id: challenge.challenger.clone(),
+        }
+        .emit();
+        Event::Refund {
+            account_id: challenge.challenger.clone(),
+            token_id: challenge.currency.token_id().cloned(),
+            amount: challenge.stake,
+        }
+        .emit();
+        challenge
+            .currency
+            .transfer(challenge.challenger, challenge.stake)
+    }
+
+    /// Escrow the stake of `amount` and post a challenge.
+    pub(crate) fn open_challenge(
+        &mut self,
+        sender_id: AccountId,
+        currency: Currency,
+        amount: U128,
+        params: ChallengeParams,
+    ) -> Result<ChallengeId, String> {
+        self.check_not_paused()?;
+        self.check_stake(&currency, amount.0)?;
+        let streamed = currency.is_streamed() && self.streaming_id.is_some();
+        params.game.validate(amount.0, streamed)?;
+        if params.min_rating.unwrap_or(0) > params.max_rating.unwrap_or(u32::MAX) {
+            return Err("min_rating can't be above max_rating".to_string());
+        }
+        if params.game.opponent.as_ref() == Some(&sender_id) {
+            return Err("can't challenge yourself".to_string());
+        }
+        self.use_game_storage(&sender_id)?;
+
+        self.challenge_id += 1;
+        let challenge_id = self.challenge_id;
+        Event::ChallengePosted {
+            challenge_id,
+            account_id: sender_id.clone(),
+            token_id: currency.token_id().cloned(),
+            amount,
+        }
+        .emit();
+        let challenge = Challenge {
+            challenge_id,
+            challenger: sender_id,
+            currency,
+            stake: amount,
+            params,
+        };
+        self.challenges.insert(&challenge_id, &challenge);
+        Ok(challenge_id)
+    }
+
+    /// Accept the challenge `challenge_id` with the same stake, putting it in
+    /// line to be played, and start the next game in line if the contract
+    /// is free. Returns the promise setting it up, if there is one.
+    pub(crate) fn accept_open_challenge(
+        &mut self,
+        sender_id: AccountId,
+        currency: Currency,
+        amount: U128,
+        challenge_id: ChallengeId,
+    ) -> Result<Option<Promise>, String> {
+        self.check_not_paused()?;
+        let challenge = self
+            .challenges
+            .get(&challenge_id)
+            .ok_or_else(|| format!("challenge {} is not open", challenge_id))?;
+        if challenge.challenger == sender_id {
+            return Err("can't accept your own challenge".to_string());
+        }
+        if challenge
+            .params
+            .game
+            .opponent
+            .as_ref()
+            .is_some_and(|opponent| opponent != &sender_id)
+        {
+            return Err(format!("challenge {} is for another player", challenge_id));
+        }
+        if challenge.currency != currency || challenge.stake != amount {
+            return Err(format!(
+                "stake should be: {} {}",
+                challenge.stake.0, challenge.currency
+            ));
+        }
+        let rating = self
+            .profiles
+            .get(&sender_id)
+            .map_or(INITIAL_RATING, |profile| profile.rating);
+        let params = &challenge.params;
+        if rating < params.min_rating.unwrap_or(0) || rating > params.max_rating.unwrap_or(u32::MAX)
+        {
+            return Err(format!("rating {} is out of the challenge's range", rating));
+        }
+
+        self.challenges.remove(&challenge_id);
+        Event::ChallengeAccepted {
+            challenge_id,
+            account_id: sender_id.clone(),
+        }
+        .emit();
+        self.accepted.insert(
+            &challenge_id,
+            &AcceptedChallenge {
+                challenge,
+                opponent: sender_id,
+            },
+        );
+        Ok(self.start_accepted_challenge())
+    }
+
+    /// Make the oldest accepted challenge the game, if the contract is free:
+    /// nobody is registered and it isn't paused. Returns the promise setting
+    /// the game up, if there is one.
+    pub(crate) fn start_accepted_challenge(&mut self) -> Option<Promise> {
+        if self.paused || self.game.is_some() || self.first.is_some() {
+            return None;
+        }
+        let challenge_id = self.accepted.min()?;
+        let accepted = self.accepted.remove(&challenge_id).unwrap();
+        let challenge = accepted.challenge;
+        self.streamed = challenge.currency.is_streamed() && self.streaming_id.is_some();
+        self.register_first_player(
+            challenge.challenger,
+            challenge.currency.clone(),
+            challenge.stake,
+            challenge.params.game,
+        );
+        Event::ChallengeStarted {
+            challenge_id,
+            game_id: self.game_id,
+        }
+        .emit();
+        self.join_open_game(
+            accepted.opponent,
+            challenge.currency,
+            challenge.stake,
+            self.game_id,
+        )
+        .unwrap_or_else(|reason| env::panic_str(&reason));
+        match self.start_registered_game() {
+            PromiseOrValue::Promise(promise) => Some(promise),
+            PromiseOrValue::Value(_) => None,
+        }
+    }
+
+    /// Withdraw an accepted challenge before it is played, refunding both
+    /// players. Only they can.
+    fn cancel_accepted_challenge(&mut self, accepted: AcceptedChallenge) -> Promise {
+        let caller = env::predecessor_account_id();
+        let challenge = accepted.challenge;
+        require!(
+            caller == challenge.challenger || caller == accepted.opponent,
+            "only the players can cancel an accepted challenge"
+        );
+        self.accepted.remove(&challenge.challenge_id);
+        self.release_game_storage(&challenge.challenger);
+        Event::ChallengeCancelled {
+            challenge_id: challenge.challenge_id,
+            account_id: caller,
+        }
+        .emit();
+        [challenge.challenger, accepted.opponent]
+            .map(|account_id| {
+                Event::Refund {
+                    account_id: account_id.clone(),
+                    token_id: challenge.currency.token_id().cloned(),
+                    amount: challenge.stake,
+                }
+                .emit();
+                challenge.currency.transfer(account_id, challenge.stake)
+            })
+            .into_iter()
+            .reduce(Promise::and)
+            .unwrap()
+    }
+}
@@ -0,0 +1,190 @@
+//! Players' time kept by the contract itself, for games that are not
+//! streamed by roke.to.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::Serialize;
+
+use crate::game_options::TimeControl;
+use crate::player::GamePlayerIndex;
+
+const NANOS: u64 = 1_000_000_000;
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ChessClock {
+    /// Nanoseconds left to each player when their turn started.
+    banks: [u64; 2],
+    increment: u64,
+    delay: u64,
+    /// Player whose time runs, and the timestamp their turn started at.
+    running: Option<(GamePlayerIndex, u64)>,
+    /// Part of the delay of a held turn already used up.
+    delay_used: u64,
+}
+
+impl ChessClock {
+    pub fn new(time_control: &TimeControl) -> Self {
+        Self {
+            banks: [time_control.seconds * NANOS; 2],
+            increment: time_control.increment * NANOS,
+            delay: time_control.delay * NANOS,
+            running: None,
+            delay_used: 0,
+        }
+    }
+
+    /// Start `player`'s turn at `now`.
+    pub fn start(&mut self, player: GamePlayerIndex, now: u64) {
+        self.running = Some((player, now));
+        self.delay_used = 0;
+    }
+
+    /// Go on with `player`'s held turn at `now`, with what was left of the
+    /// delay when it was held.
+    pub fn resume(&mut self, player: GamePlayerIndex, now: u64) {
+        self.running = Some((player, now.saturating_sub(self.delay_used)));
+    }
+
+    /// End the running player's turn at `now`, adding the increment.
+    pub fn stop(&mut self, now: u64) {
+        if let Some((player, _)) = self.running {
+            let left = self.remaining(player, now);
+            self.banks[index(player)] = left + self.increment;
+            self.running = None;
+        }
+    }
+
+    /// Freeze the running player's time at `now`, without the increment:
+    /// their turn goes on when the clock is resumed.
+    pub fn hold(&mut self, now: u64) {
+        if let Some((player, since)) = self.running {
+            self.banks[index(player)] = self.remaining(player, now);
+            self.delay_used = now.saturating_sub(since).min(self.delay);
+            self.running = None;
+        }
+    }
+
+    /// End the running player's turn at `now` and start `next`'s.
+    pub fn switch(&mut self, next: GamePlayerIndex, now: u64) {
+        self.stop(now);
+        self.start(next, now);
+    }
+
+    /// Nanoseconds `player` has left at `now`.
+    pub fn remaining(&self, player: GamePlayerIndex, now: u64) -> u64 {
+        let bank = self.banks[index(player)];
+        match self.running {
+            Some((running, since)) if running == player => {
+                let spent = now.saturating_sub(since).saturating_sub(self.delay);
+                bank.saturating_sub(spent)
+            }
+            _ => bank,
+        }
+    }
+
+    /// The running player, if their time is over at `now`.
+    pub fn flagged(&self, now: u64) -> Option<GamePlayerIndex> {
+        self.running
+            .map(|(player, _)| player)
+            .filter(|&player| self.remaining(player, now) == 0)
+    }
+
+    pub fn view(&self, now: u64) -> ClockView {
+        ClockView {
+            remaining: [1, 2].map(|player| U64(self.remaining(player, now))),
+            running: self.running.map(|(player, _)| player),
+            increment: U64(self.increment),
+            delay: U64(self.delay),
+        }
+    }
+}
+
+fn index(player: GamePlayerIndex) -> usize {
+    match player {
+        1 => 0,
+        2 => 1,
+        _ => unreachable!("Game Players only have indexes 1 and 2."),
+    }
+}
+
+/// Players' clocks, all times in nanoseconds.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClockView {
+    /// Time left to players 1 and 2.
+    pub remaining: [U64; 2],
+    /// Player whose time is running.
+    pub running: Option<GamePlayerIndex>,
+    pub increment: U64,
+    pub delay: U64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_clock(seconds: u64, increment: u64, delay: u64) -> ChessClock {
+        ChessClock::new(&TimeControl {
+            seconds,
+            increment,
+            delay,
+        })
+    }
+
+    #[test]
+    fn test_clock_runs_for_active_player() {
+        let mut clock = new_clock(60, 0, 0);
+        clock.start(1, 0);
+        assert_eq!(clock.remaining(1, 10 * NANOS), 50 * NANOS);
+        assert_eq!(clock.remaining(2, 10 * NANOS), 60 * NANOS);
+
+        clock.switch(2, 10 * NANOS);
+        assert_eq!(clock.remaining(1, 30 * NANOS), 50 * NANOS);
+        assert_eq!(clock.remaining(2, 30 * NANOS), 40 * NANOS);
+        assert_eq!(clock.flagged(69 * NANOS), None);
+        assert_eq!(clock.flagged(70 * NANOS), Some(2));
+        assert_eq!(clock.remaining(2, 100 * NANOS), 0);
+    }
+
+    #[test]
+    fn test_increment_and_delay() {
+        let mut clock = new_clock(60, 5, 0);
+        clock.start(1, 0);
+        clock.switch(2, 10 * NANOS);
+        assert_eq!(clock.remaining(1, 10 * NANOS), 55 * NANOS);
+
+        let mut clock = new_clock(60, 0, 3);
+        clock.start(1, 0);
+        // first 3 seconds of the turn are free
+        assert_eq!(clock.remaining(1, 2 * NANOS), 60 * NANOS);
+        clock.switch(2, 10 * NANOS);
+        assert_eq!(clock.remaining(1, 10 * NANOS), 53 * NANOS);
+        assert_eq!(
+            clock.view(10 * NANOS),
+            ClockView {
+                remaining: [U64(53 * NANOS), U64(60 * NANOS)],
+                running: Some(2),
+                increment: U64(0),
+                delay: U64(3 * NANOS),
+            }
+        );
+    }
+
+    #[test]
+    fn test_hold_keeps_the_delay_left() {
+        let mut clock = new_clock(60, 0, 3);
+        clock.start(1, 0);
+        clock.hold(2 * NANOS);
+        assert_eq!(clock.remaining(1, 100 * NANOS), 60 * NANOS);
+        // 1 second of the delay is left after the hold
+        clock.resume(1, 100 * NANOS);
+        assert_eq!(clock.remaining(1, 101 * NANOS), 60 * NANOS);
+        assert_eq!(clock.remaining(1, 105 * NANOS), 56 * NANOS);
+
+        // a turn held after its delay ran out gets none back
+        clock.hold(105 * NANOS);
+        clock.resume(1, 200 * NANOS);
+        assert_eq!(clock.remaining(1, 201 * NANOS), 55 * NANOS);
+    }
+}
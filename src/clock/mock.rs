@@ -0,0 +1,155 @@
+//! In-memory streams for offline tests. Installed for the current test
+//! thread, it replaces roke.to as the contract's clock backend: stream
+//! operations change the mock's state at once, using the block timestamp
+//! of the mocked blockchain, and return empty promises.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{env, AccountId, Promise};
+
+use super::{ClockBackend, NewStream, StreamStatus};
+
+#[derive(Clone, Debug)]
+pub struct MockStream {
+    pub receiver_id: AccountId,
+    pub balance: u128,
+    pub tokens_per_sec: u128,
+    pub withdrawn: u128,
+    pub status: StreamStatus,
+    last_action: u64,
+}
+
+impl MockStream {
+    /// Pay out what was streamed since the last action.
+    fn accrue(&mut self) {
+        let now = env::block_timestamp();
+        if self.status == StreamStatus::Active {
+            let seconds = u128::from((now - self.last_action) / 1_000_000_000);
+            let streamed = self.tokens_per_sec.saturating_mul(seconds);
+            self.withdrawn = self.balance.min(self.withdrawn.saturating_add(streamed));
+        }
+        self.last_action = now;
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockClock {
+    streams: Rc<RefCell<HashMap<String, MockStream>>>,
+}
+
+thread_local! {
+    static INSTALLED: RefCell<Option<MockClock>> = const { RefCell::new(None) };
+}
+
+impl MockClock {
+    /// A new mock, used by contracts on this thread instead of roke.to.
+    pub fn install() -> Self {
+        let clock = Self::default();
+        INSTALLED.with(|installed| *installed.borrow_mut() = Some(clock.clone()));
+        clock
+    }
+
+    /// ID of the stream created last.
+    pub fn last_created_stream(&self) -> String {
+        format!("stream-{}", self.streams.borrow().len())
+    }
+
+    pub fn stream(&self, stream_id: &str) -> MockStream {
+        let mut stream = self.streams.borrow()[stream_id].clone();
+        stream.accrue();
+        stream
+    }
+
+    /// The stream as roke.to's `get_stream` returns it.
+    pub fn stream_json(&self, stream_id: &str) -> HashMap<String, Value> {
+        let stream = self.stream(stream_id);
+        HashMap::from([
+            ("id".to_string(), json!(stream_id)),
+            ("balance".to_string(), json!(stream.balance.to_string())),
+            (
+                "tokens_total_withdrawn".to_string(),
+                json!(stream.withdrawn.to_string()),
+            ),
+            ("status".to_string(), json!(format!("{:?}", stream.status))),
+        ])
+    }
+
+    /// The contract's account as roke.to's `get_account` returns it.
+    pub fn account_json(&self) -> HashMap<String, Value> {
+        HashMap::from([(
+            "last_created_stream".to_string(),
+            json!(self.last_created_stream()),
+        )])
+    }
+
+    /// Put the stream in `status` behind the contract's back, as if an
+    /// action on it failed.
+    pub fn set_status(&self, stream_id: &str, status: StreamStatus) {
+        let mut streams = self.streams.borrow_mut();
+        let stream = streams.get_mut(stream_id).unwrap();
+        stream.accrue();
+        stream.status = status;
+    }
+
+    fn update(&self, stream_id: &str, status: StreamStatus) -> Promise {
+        let mut streams = self.streams.borrow_mut();
+        let stream = streams
+            .get_mut(stream_id)
+            .unwrap_or_else(|| panic!("no stream {}", stream_id));
+        assert!(
+            stream.status != StreamStatus::Finished,
+            "stream {} is finished",
+            stream_id
+        );
+        stream.accrue();
+        stream.status = status;
+        Promise::new(env::current_account_id())
+    }
+}
+
+impl ClockBackend for MockClock {
+    /// The installed mock, or a new one if none is.
+    fn connect(_streaming_id: AccountId) -> Self {
+        INSTALLED.with(|installed| installed.borrow().clone().unwrap_or_default())
+    }
+
+    fn create_stream(&self, stream: NewStream) -> Promise {
+        let mut streams = self.streams.borrow_mut();
+        let stream_id = format!("stream-{}", streams.len() + 1);
+        streams.insert(
+            stream_id,
+            MockStream {
+                receiver_id: stream.receiver_id,
+                balance: stream.balance.0,
+                tokens_per_sec: stream.tokens_per_sec.0,
+                withdrawn: 0,
+                status: StreamStatus::Initialized,
+                last_action: env::block_timestamp(),
+            },
+        );
+        Promise::new(env::current_account_id())
+    }
+
+    fn get_account(&self, _account_id: AccountId) -> Promise {
+        Promise::new(env::current_account_id())
+    }
+
+    fn start_stream(&self, stream_id: String) -> Promise {
+        self.update(&stream_id, StreamStatus::Active)
+    }
+
+    fn pause_stream(&self, stream_id: String) -> Promise {
+        self.update(&stream_id, StreamStatus::Paused)
+    }
+
+    fn stop_stream(&self, stream_id: String) -> Promise {
+        self.update(&stream_id, StreamStatus::Finished)
+    }
+
+    fn get_stream(&self, _stream_id: String) -> Promise {
+        Promise::new(env::current_account_id())
+    }
+}
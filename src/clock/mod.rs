@@ -0,0 +1,160 @@
+//! Players' clocks: while a player thinks, the opponent's stream pays the
+//! opponent back from their own stake. The game only needs to start, pause,
+//! stop and read the streams, whoever runs them.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+pub(crate) mod mock;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, Value};
+use near_sdk::{env, AccountId, Gas, Promise};
+
+use crate::external::streaming_roketo::streaming_roketo::{self, StreamingRoketoExt};
+use crate::external::{token::token, TGAS};
+
+/// Backend the contract runs the players' streams with.
+#[cfg(not(test))]
+pub type Backend = Roketo;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+pub type Backend = mock::MockClock;
+
+/// Status of a player's stream.
+#[derive(
+    Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StreamStatus {
+    Initialized,
+    Active,
+    Paused,
+    Finished,
+}
+
+impl StreamStatus {
+    /// Parse the `status` of a stream returned by `get_stream`: roke.to gives
+    /// either a name, or an object like `{"Finished": {...}}`.
+    pub fn from_json(status: &Value) -> Option<Self> {
+        let name = match status {
+            Value::String(name) => name.as_str(),
+            Value::Object(object) => object.keys().next()?.as_str(),
+            _ => return None,
+        };
+        match name {
+            "Initialized" => Some(Self::Initialized),
+            "Active" => Some(Self::Active),
+            "Paused" => Some(Self::Paused),
+            "Finished" => Some(Self::Finished),
+            _ => None,
+        }
+    }
+}
+
+/// A new stream of a player's stake, paid out to the player.
+pub struct NewStream {
+    pub token_id: AccountId,
+    pub receiver_id: AccountId,
+    pub balance: U128,
+    pub tokens_per_sec: U128,
+    pub description: String,
+}
+
+/// Controls the players' streams.
+pub trait ClockBackend {
+    /// The backend of the streaming contract `streaming_id`.
+    fn connect(streaming_id: AccountId) -> Self;
+
+    /// Deposit the stake into a new stream, created paused. Resolves to the
+    /// amount of tokens the streaming contract used.
+    fn create_stream(&self, stream: NewStream) -> Promise;
+
+    /// Resolves to the streaming account of `account_id` as a JSON object
+    /// with `last_created_stream`, the ID of its latest stream.
+    fn get_account(&self, account_id: AccountId) -> Promise;
+
+    fn start_stream(&self, stream_id: String) -> Promise;
+
+    fn pause_stream(&self, stream_id: String) -> Promise;
+
+    fn stop_stream(&self, stream_id: String) -> Promise;
+
+    /// Resolves to the stream as a JSON object with `tokens_total_withdrawn`,
+    /// the part of the stake paid to the receiver.
+    fn get_stream(&self, stream_id: String) -> Promise;
+}
+
+/// Streams run by the roke.to streaming contract.
+pub struct Roketo {
+    streaming_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct RoketoStreamingCreateRequest {
+    balance: String,
+    owner_id: AccountId,
+    receiver_id: AccountId,
+    token_name: AccountId,
+    tokens_per_sec: String,
+    is_locked: bool,
+    is_auto_start_enabled: bool,
+    description: String,
+}
+
+impl Roketo {
+    fn streaming(&self) -> StreamingRoketoExt {
+        streaming_roketo::ext(self.streaming_id.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(Gas(60 * TGAS))
+    }
+}
+
+impl ClockBackend for Roketo {
+    fn connect(streaming_id: AccountId) -> Self {
+        Self { streaming_id }
+    }
+
+    fn create_stream(&self, stream: NewStream) -> Promise {
+        let memo = format!("Roketo transfer: {}", stream.receiver_id);
+        let request = RoketoStreamingCreateRequest {
+            balance: stream.balance.0.to_string(),
+            owner_id: env::current_account_id(),
+            receiver_id: stream.receiver_id,
+            token_name: stream.token_id.clone(),
+            tokens_per_sec: stream.tokens_per_sec.0.to_string(),
+            is_locked: false,
+            is_auto_start_enabled: false,
+            description: stream.description,
+        };
+        let request_json = serde_json::to_string(&request).expect("failed to serialize request");
+        let msg = format!("{{\"Create\": {{ \"request\": {request_json} }}}}");
+
+        token::ext(stream.token_id)
+            .with_static_gas(Gas(60 * TGAS))
+            .with_attached_deposit(1)
+            .ft_transfer_call(self.streaming_id.clone(), stream.balance, memo, msg)
+    }
+
+    fn get_account(&self, account_id: AccountId) -> Promise {
+        streaming_roketo::ext(self.streaming_id.clone()).get_account(account_id)
+    }
+
+    fn start_stream(&self, stream_id: String) -> Promise {
+        self.streaming().start_stream(stream_id)
+    }
+
+    fn pause_stream(&self, stream_id: String) -> Promise {
+        self.streaming().pause_stream(stream_id)
+    }
+
+    fn stop_stream(&self, stream_id: String) -> Promise {
+        self.streaming().stop_stream(stream_id)
+    }
+
+    fn get_stream(&self, stream_id: String) -> Promise {
+        self.streaming().get_stream(stream_id)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, Promise};
+
+use crate::external::token::token;
+
+/// What a game is played for.
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    BorshDeserialize,
+    BorshSerialize,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Currency {
+    /// Native NEAR attached to `create_game`/`join_game`. Kept in escrow by
+    /// the contract: roke.to streams fungible tokens only.
+    Near,
+    /// Fungible token deposited with `ft_transfer_call` and streamed by roke.to.
+    Token(AccountId),
+}
+
+impl Currency {
+    /// Token contract, `None` for native NEAR.
+    pub fn token_id(&self) -> Option<&AccountId> {
+        match self {
+            Currency::Near => None,
+            Currency::Token(token_id) => Some(token_id),
+        }
+    }
+
+    /// Whether players' stakes are streamed by roke.to while they think.
+    pub fn is_streamed(&self) -> bool {
+        self.token_id().is_some()
+    }
+
+    /// Send `amount` to `receiver_id`.
+    pub fn transfer(&self, receiver_id: AccountId, amount: U128) -> Promise {
+        match self {
+            Currency::Near => Promise::new(receiver_id).transfer(amount.0),
+            Currency::Token(token_id) => token::ext(token_id.clone())
+                .with_attached_deposit(1)
+                .ft_transfer(receiver_id, amount, None),
+        }
+    }
+}
+
+/// An amount of some currency.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Balance {
+    /// `None` for native NEAR.
+    pub token_id: Option<AccountId>,
+    pub amount: U128,
+}
+
+impl Balance {
+    pub fn new(currency: &Currency, amount: u128) -> Self {
+        Self {
+            token_id: currency.token_id().cloned(),
+            amount: U128(amount),
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Currency::Near => write!(f, "NEAR"),
+            Currency::Token(token_id) => write!(f, "{}", token_id),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Structured event logs following NEP-297:
+//! `EVENT_JSON:{"standard":"deblockle","version":"1.0.0","event":"roll","data":{...}}`
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{log, serde_json, AccountId};
+
+use crate::challenges::ChallengeId;
+use crate::game::{Game, GameId, GamePhase};
+use crate::game_options::Side;
+use crate::player::GamePlayerIndex;
+use crate::position::GameCubePosition;
+
+pub const EVENT_STANDARD: &str = "deblockle";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    PlayerRegistered {
+        account_id: AccountId,
+        player: GamePlayerIndex,
+        /// `None` for native NEAR.
+        token_id: Option<AccountId>,
+        amount: U128,
+    },
+    /// `side` is what the creator asked for, `first` moves first.
+    SidesAssigned {
+        first: AccountId,
+        second: AccountId,
+        side: Side,
+    },
+    GameStarted {
+        first: AccountId,
+        second: AccountId,
+        num_cubes: usize,
+    },
+    Roll {
+        player: GamePlayerIndex,
+        from: String,
+        to: String,
+        /// Face index facing up after the roll.
+        up: i8,
+    },
+    Hop {
+        player: GamePlayerIndex,
+        from: String,
+        to: String,
+    },
+    Pass {
+        player: GamePlayerIndex,
+    },
+    Score {
+        player: GamePlayerIndex,
+        cubes_left: usize,
+    },
+    /// The player ran out of time on the contract's clock and lost.
+    FlagFall {
+        player: GamePlayerIndex,
+    },
+    GameOver {
+        winner: AccountId,
+        player: GamePlayerIndex,
+    },
+    StreamStarted {
+        account_id: AccountId,
+        stream_id: String,
+    },
+    StreamPaused {
+        account_id: AccountId,
+        stream_id: String,
+    },
+    StreamStopped {
+        account_id: AccountId,
+        stream_id: String,
+    },
+    RegistrationCancelled {
+        account_id: AccountId,
+    },
+    RegistrationFailed {
+        reason: String,
+    },
+    Refund {
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    },
+    Payout {
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    },
+    ChallengePosted {
+        challenge_id: ChallengeId,
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    },
+    /// `account_id` accepted the challenge, putting it in line to be played.
+    ChallengeAccepted {
+        challenge_id: ChallengeId,
+        account_id: AccountId,
+    },
+    /// The accepted challenge became the game `game_id`.
+    ChallengeStarted {
+        challenge_id: ChallengeId,
+        game_id: GameId,
+    },
+    ChallengeCancelled {
+        challenge_id: ChallengeId,
+        account_id: AccountId,
+    },
+    /// The owner halted play and registrations.
+    Paused {
+        account_id: AccountId,
+    },
+    Unpaused {
+        account_id: AccountId,
+    },
+    /// The payout wasn't delivered and became claimable.
+    PayoutFailed {
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl Event {
+    pub fn to_json(&self) -> String {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        serde_json::to_string(&log).expect("failed to serialize event")
+    }
+
+    pub fn emit(&self) {
+        log!("EVENT_JSON:{}", self.to_json());
+    }
+}
+
+/// Events describing a move from `from` to `to` that turned `before` into
+/// `after`. Empty if the move was illegal.
+pub fn move_events(
+    before: &Game,
+    after: &Game,
+    from: GameCubePosition,
+    to: GameCubePosition,
+) -> Vec<Event> {
+    if !before.legal_targets(from).contains(&to) {
+        return vec![];
+    }
+    let player = before.active_player;
+    match before.phase {
+        GamePhase::Roll => {
+            // a cube rolled Star up into the win square leaves the board
+            let scored = after.get_cube_at(to).is_none();
+            let up = after.get_cube_at(to).map_or(1, |cube| cube.direction.up);
+            let mut events = vec![Event::Roll {
+                player,
+                from: from.to_string(),
+                to: to.to_string(),
+                up,
+            }];
+            if scored {
+                let cubes_left = after.board.iter().filter(|c| c.player == player).count();
+                events.push(Event::Score { player, cubes_left });
+            }
+            events
+        }
+        GamePhase::Hop => vec![Event::Hop {
+            player,
+            from: from.to_string(),
+            to: to.to_string(),
+        }],
+        GamePhase::End => vec![],
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::Pass { player: 2 };
+        assert_eq!(
+            event.to_json(),
+            r#"{"standard":"deblockle","version":"1.0.0","event":"pass","data":{"player":2}}"#
+        );
+    }
+
+    #[test]
+    fn test_move_events() {
+        let before = Game::game_setup(Some(1));
+        let from = GameCubePosition::from_square("d7").unwrap();
+        let to = GameCubePosition::from_square("c7").unwrap();
+
+        let mut after = before.clone();
+        after.try_make_move(from, to);
+        let events = move_events(&before, &after, from, to);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].to_json(),
+            r#"{"standard":"deblockle","version":"1.0.0","event":"roll","data":{"player":1,"from":"d7","to":"c7","up":6}}"#
+        );
+
+        // illegal moves don't change the game and emit nothing
+        let to = GameCubePosition::from_square("e7").unwrap();
+        let mut after = before.clone();
+        after.try_make_move(from, to);
+        assert!(move_events(&before, &after, from, to).is_empty());
+    }
+}
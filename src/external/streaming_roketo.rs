@@ -1,5 +1,6 @@
 use near_sdk::{ext_contract, AccountId};
 
+#[allow(dead_code)]
 #[ext_contract(streaming_roketo)]
 trait StreamingRoketo {
     fn get_account(account_id: AccountId) -> String;
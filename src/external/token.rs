@@ -1,6 +1,9 @@
 use near_sdk::{ext_contract, json_types::U128, AccountId};
 
+#[allow(dead_code)]
 #[ext_contract(token)]
 trait Token {
+    fn ft_transfer(receiver_id: AccountId, amount: U128, memo: Option<String>);
+
     fn ft_transfer_call(receiver_id: AccountId, amount: U128, memo: String, msg: String);
 }
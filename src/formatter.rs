@@ -6,7 +6,7 @@ use crate::position::GameCubePosition;
 impl Game {
     fn format_board_row(&self, y: i8) -> String {
         assert!((1..=7).contains(&y));
-        let v = vec![1i8, 2i8, 3i8, 4i8, 5i8, 6i8, 7i8];
+        let v = [1i8, 2i8, 3i8, 4i8, 5i8, 6i8, 7i8];
         let vs: Vec<String> = v
             .iter()
             .map(|x| {
@@ -43,7 +43,7 @@ impl Game {
     }
 
     pub fn format_board(&self) -> String {
-        let v = vec![1i8, 2i8, 3i8, 4i8, 5i8, 6i8, 7i8];
+        let v = [1i8, 2i8, 3i8, 4i8, 5i8, 6i8, 7i8];
         let rows: Vec<String> = v.iter().map(|y| self.format_board_row(*y)).collect();
         format!("  :a  b  c  d  e  f  g\n  :1  2  3  4  5  6  7  \n{r1}\n{r2}\n{r3}\n{r4}\n{r5}\n{r6}\n{r7}",
         r1 = rows[0],
@@ -39,6 +39,9 @@ impl From<i8> for GamePhase {
 }
 */
 
+/// Sequential number of a game played on the contract.
+pub type GameId = u64;
+
 /// Deblockle Game struct
 #[derive(Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -72,31 +75,18 @@ impl Game {
 
     /// Returns clone of the cube, therefore &game
     pub fn get_cube_at(&self, pos: GameCubePosition) -> Option<GameCube> {
-        let board = self.board.clone();
-        for cube in board {
-            if cube.position == pos {
-                return Some(cube);
-            }
-        }
-        None
+        self.board.iter().find(|cube| cube.position == pos).copied()
     }
 
     /// Returns reference to the cube, therefore &mut game
     fn take_cube_at(&mut self, pos: GameCubePosition) -> Option<&mut GameCube> {
-        // let board = game.board.clone();
-        let board = &mut self.board;
-        for cube in board {
-            if cube.position == pos {
-                return Some(cube);
-            }
-        }
-        None
+        self.board.iter_mut().find(|cube| cube.position == pos)
     }
 
     fn remove_cube_at(&mut self, pos: GameCubePosition) {
         let index = self.board.iter().position(|cube| cube.position == pos);
-        if let Some(..) = index {
-            self.board.remove(index.unwrap());
+        if let Some(index) = index {
+            self.board.remove(index);
         }
     }
 
@@ -106,9 +96,7 @@ impl Game {
         to: GameCubePosition,
         direction: GameCubeDirection,
     ) {
-        let opt_cube = self.take_cube_at(from);
-        if let Some(..) = opt_cube {
-            let mut cube: &mut GameCube = opt_cube.unwrap();
+        if let Some(cube) = self.take_cube_at(from) {
             cube.position = to;
             cube.direction = direction;
         }
@@ -125,6 +113,31 @@ impl Game {
         self.filter_blocked(coord_pattern_to_positions(pattern))
     }
 
+    /// Pass the rest of the turn: the other player takes turn in a Roll phase.
+    /// Returns `None` if the game is already finished.
+    pub fn pass_turn(&mut self) -> Option<String> {
+        if self.phase == GamePhase::End {
+            return None;
+        }
+        let active_player_ind = self.active_player;
+        let other_player_ind = 3 - active_player_ind;
+        self.phase = GamePhase::Roll;
+        self.active_player = other_player_ind;
+        Some(format!(
+            "Player {} passed the turn. It is player {} Roll phase.",
+            active_player_ind, other_player_ind
+        ))
+    }
+
+    /// End the game lost by `player`, e.g. on time.
+    pub fn forfeit(&mut self, player: GamePlayerIndex) -> String {
+        let winner = 3 - player;
+        self.phase = GamePhase::End;
+        // the winner of a finished game is the active player
+        self.active_player = winner;
+        format!("Player {} forfeits. Player {} wins.", player, winner)
+    }
+
     pub fn try_make_move(&mut self, from: GameCubePosition, to: GameCubePosition) -> String {
         let active_player_ind = self.active_player;
         let other_player_ind = 3 - active_player_ind;
@@ -463,6 +476,87 @@ pub(crate) mod tests {
         println!("{}", game.format_board());
     }
 
+    #[test]
+    fn test_legal_moves() {
+        let game = make_test_game_1_1();
+        let from = GameCubePosition { x: 4, y: 7 };
+        // rolling right turns Star up outside of the win square,
+        // rolling forward lands on the opponent's win square
+        assert_eq!(
+            game.legal_moves(),
+            vec![(from, GameCubePosition { x: 3, y: 7 })]
+        );
+        assert!(game
+            .legal_targets(GameCubePosition { x: 4, y: 1 })
+            .is_empty());
+
+        let mut game = make_test_game_1_1();
+        game.try_make_move(from, GameCubePosition { x: 5, y: 7 });
+        assert!(game.get_cube_at(from).is_some());
+    }
+
+    #[test]
+    fn test_board_view() {
+        let view = make_test_game_1_1().board_view();
+        assert_eq!(view.rows.len(), 7);
+        assert!(view.rows.iter().all(|row| row.len() == 7));
+        assert_eq!(view.rows[1][3].win_square_of, Some(1));
+        assert_eq!(view.rows[5][3].win_square_of, Some(2));
+
+        let cell = &view.rows[6][3];
+        assert_eq!(cell.square, "d7");
+        let cube = cell.cube.as_ref().unwrap();
+        let faces = &cube.faces;
+        assert_eq!((faces.up, faces.front, faces.right), (2, 3, 6));
+        assert_eq!((faces.down, faces.back, faces.left), (5, 4, 1));
+        assert!(cube.selectable);
+        assert_eq!(cube.targets, vec![GameCubePosition { x: 3, y: 7 }]);
+
+        let other = view.rows[0][3].cube.as_ref().unwrap();
+        assert!(!other.selectable);
+    }
+
+    #[test]
+    fn test_svg_board() {
+        let game = Game::game_setup(None);
+        let svg = game.format_svg(&crate::svg::SvgOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"480\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("rx=\"8\"").count(), 8);
+        assert_eq!(svg.matches("fill=\"#2b6cb0\"/>").count(), 4);
+        assert_eq!(svg.matches("&#9733;").count(), 2);
+        assert!(!svg.contains("<circle") && !svg.contains("<line"));
+
+        let options = crate::svg::SvgOptions {
+            last_move: crate::notation::GameMove::parse("d7-c7"),
+            legal_moves: true,
+        };
+        let svg = make_test_game_1_1().format_svg(&options);
+        // the only legal move, d7-c7, is highlighted and drawn as the last one
+        assert_eq!(svg.matches("<circle cx=\"180\" cy=\"420\"").count(), 1);
+        assert!(svg.contains("<line x1=\"240\" y1=\"420\" x2=\"180\" y2=\"420\""));
+    }
+
+    #[test]
+    fn test_ansi_board() {
+        let board = Game::game_setup(None).format_board_ansi();
+        let lines: Vec<&str> = board.lines().collect();
+        // header, 7 rows with borders between them, bottom border and legend
+        assert_eq!(lines.len(), 17);
+        assert!(lines[0].starts_with("      a       b"));
+        assert!(lines[1].starts_with("  ┌───────┬"));
+        assert!(lines[15].starts_with("  └───────┴"));
+        assert_eq!(board.matches("\x1b[34m ").count(), 4);
+        assert_eq!(board.matches("\x1b[31m ").count(), 4);
+        assert_eq!(board.matches('★').count(), 2);
+
+        // the cube on d7 shows XHop up, Slide in front and Stop on the right
+        let row = make_test_game_1_1().format_board_ansi();
+        let row = row.lines().nth(14).unwrap();
+        assert!(row.starts_with("7 │"));
+        assert!(row.contains("\x1b[34m \x1b[1mX\x1b[0m\x1b[34m\x1b[2m ↑L→P\x1b[0m"));
+    }
+
     #[test]
     fn test_move_pattern() {
         let mut game = make_test_game_1_1();
@@ -0,0 +1,57 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+/// Thinking time assumed for a game without an explicit time control, in seconds.
+pub const DEFAULT_GAME_SECONDS: u64 = 10 * 60;
+
+/// Game rules variant.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Ruleset {
+    /// Rules as implemented by this contract, with the altered "sLide" and
+    /// "Hoops" hops (see README).
+    #[default]
+    Hackathon,
+}
+
+/// Side the creator of a game takes.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Side {
+    /// Player 1, who moves first.
+    #[default]
+    First,
+    Second,
+    /// Drawn from the block's random seed when the opponent joins.
+    Random,
+}
+
+/// How much time the players have for the game.
+#[derive(
+    Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimeControl {
+    /// Thinking time of each player, in seconds.
+    pub seconds: u64,
+    /// Seconds added to a player's time after each turn (on-contract clock only).
+    #[serde(default)]
+    pub increment: u64,
+    /// Seconds at the start of each turn that don't count (on-contract clock only).
+    #[serde(default)]
+    pub delay: u64,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            seconds: DEFAULT_GAME_SECONDS,
+            increment: 0,
+            delay: 0,
+        }
+    }
+}
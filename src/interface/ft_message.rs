@@ -0,0 +1,144 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+use crate::challenges::{ChallengeId, ChallengeParams};
+use crate::game::GameId;
+use crate::game_options::{Ruleset, Side, TimeControl};
+
+/// Message passed with `ft_transfer_call` to the game contract, e.g.
+/// `{"CreateGame": {"tokens_per_sec": "10000"}}` or `{"JoinGame": {"game_id": 1}}`.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TransferMessage {
+    CreateGame(GameParams),
+    JoinGame { game_id: GameId },
+    PostChallenge(ChallengeParams),
+    AcceptChallenge { challenge_id: ChallengeId },
+}
+
+/// Settings of a new game, chosen by its creator.
+#[derive(Clone, Debug, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameParams {
+    /// Rate of the roke.to stream paying the opponent while a player thinks.
+    pub tokens_per_sec: U128,
+    pub num_cubes: Option<usize>,
+    pub ruleset: Option<Ruleset>,
+    pub time_control: Option<TimeControl>,
+    /// The only account allowed to join, for a private game.
+    pub opponent: Option<AccountId>,
+    /// The creator's side, `First` by default.
+    pub side: Option<Side>,
+}
+
+impl GameParams {
+    /// Check the params make sense for the stake of `deposit` tokens. The
+    /// streaming rate is only checked for a `streamed` stake.
+    pub fn validate(&self, deposit: u128, streamed: bool) -> Result<(), String> {
+        if let Some(num_cubes) = self.num_cubes {
+            if !(1..=4).contains(&num_cubes) {
+                return Err(format!("num_cubes must be in 1..4, got {}", num_cubes));
+            }
+        }
+        if !streamed {
+            return Ok(());
+        }
+        if self.tokens_per_sec.0 == 0 {
+            return Err("tokens_per_sec must be positive".to_string());
+        }
+        let seconds = self.time_control.unwrap_or_default().seconds;
+        match self.tokens_per_sec.0.checked_mul(seconds as u128) {
+            Some(streamed) if streamed <= deposit => Ok(()),
+            _ => Err(format!(
+                "streaming {} tokens/sec for {} sec exceeds the deposit of {}",
+                self.tokens_per_sec.0, seconds, deposit
+            )),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::serde_json;
+
+    use super::*;
+    use crate::game_options::DEFAULT_GAME_SECONDS;
+
+    fn create_game(msg: &str) -> GameParams {
+        match serde_json::from_str(msg).unwrap() {
+            TransferMessage::CreateGame(params) => params,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "10000"}}"#);
+        assert_eq!(params.tokens_per_sec.0, 10000);
+        assert!(params.num_cubes.is_none());
+
+        let params = create_game(
+            r#"{"CreateGame": {"tokens_per_sec": "5", "num_cubes": 2, "ruleset": "Hackathon", "time_control": {"seconds": 300}}}"#,
+        );
+        assert_eq!(params.num_cubes, Some(2));
+        assert_eq!(
+            params.time_control,
+            Some(TimeControl {
+                seconds: 300,
+                ..TimeControl::default()
+            })
+        );
+
+        let join: TransferMessage =
+            serde_json::from_str(r#"{"JoinGame": {"game_id": 7}}"#).unwrap();
+        assert!(matches!(join, TransferMessage::JoinGame { game_id: 7 }));
+
+        for bad in [
+            r#"{"tokens_per_sec": "10000"}"#,
+            r#"{"CreateGame": {"tokens_per_sec": "fast"}}"#,
+            r#"{"JoinGame": {}}"#,
+            "",
+        ] {
+            assert!(
+                serde_json::from_str::<TransferMessage>(bad).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_params() {
+        let params = create_game(
+            r#"{"CreateGame": {"tokens_per_sec": "10", "time_control": {"seconds": 60}}}"#,
+        );
+        assert!(params.validate(600, true).is_ok());
+        assert!(params.validate(599, true).is_err());
+
+        // the default time control applies when none is given
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "1"}}"#);
+        assert!(params.validate(DEFAULT_GAME_SECONDS as u128, true).is_ok());
+        assert!(params
+            .validate(DEFAULT_GAME_SECONDS as u128 - 1, true)
+            .is_err());
+
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "0"}}"#);
+        assert!(params.validate(1000, true).is_err());
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "1", "num_cubes": 5}}"#);
+        assert!(params.validate(1000, true).is_err());
+        let params = create_game(&format!(
+            r#"{{"CreateGame": {{"tokens_per_sec": "{}"}}}}"#,
+            u128::MAX
+        ));
+        assert!(params.validate(u128::MAX, true).is_err());
+
+        // without streams the rate is never used
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "0"}}"#);
+        assert!(params.validate(1, false).is_ok());
+        let params = create_game(r#"{"CreateGame": {"tokens_per_sec": "1", "num_cubes": 5}}"#);
+        assert!(params.validate(1000, false).is_err());
+    }
+}
@@ -1,20 +1,14 @@
-use std::collections::HashMap;
-
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
-use near_sdk::{
-    env, json_types::U128, log, near_bindgen, serde_json, AccountId, Gas, PromiseOrValue,
-};
+use near_sdk::{env, json_types::U128, log, near_bindgen, serde_json, AccountId, PromiseOrValue};
+
+use crate::{currency::Currency, Contract, ContractExt};
 
-use crate::{
-    external::{streaming_roketo::streaming_roketo, token::token, TGAS},
-    game::Game,
-    interface::RoketoStreamingCreateRequest,
-    player::Player,
-    Contract, ContractExt,
-};
+use super::ft_message::TransferMessage;
 
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
+    /// Registers players by their deposits. Returns the whole amount as unused,
+    /// so that the token contract refunds it, if the transfer is not accepted.
     fn ft_on_transfer(
         &mut self,
         sender_id: AccountId,
@@ -30,100 +24,36 @@ impl FungibleTokenReceiver for Contract {
             msg
         );
 
-        assert!(self.game.is_none(), "Game already started");
-
-        if self.first.is_none() {
-            let msg_values: HashMap<String, String> = serde_json::from_str(&msg).unwrap();
-            let tokens_per_sec = msg_values
-                .get(&"tokens_per_sec".to_string())
-                .expect("entry required in msg, eg: {{ \"tokens_per_sec\": \"string\" }}")
-                .clone();
-
-            self.register_first_player(sender_id, token_id, amount, tokens_per_sec)
-        } else if self.second.is_none() {
-            assert!(
-                self.token_id
-                    .as_ref()
-                    .expect("somehow token ID is NOT set yet")
-                    == &token_id,
-                "wrong token id"
-            );
-
-            assert!(
-                self.first_player().deposit() == amount,
-                "deposit should be: {amount:?}"
-            );
-            log!(
-                "second player registered: {} with deposit: {:?}, game started!",
-                sender_id,
-                amount
-            );
-            self.second = Some(Player::new(sender_id, amount, 2));
-            let first = self.first_player();
-
-            log!("create stream for first player");
-            let streaming_id = self.streaming_id();
-
-            let memo = format!("Roketo transfer: {}", first.account());
-            let current_account = env::current_account_id();
-
-            let mut request = RoketoStreamingCreateRequest {
-                balance: self.deposit.to_string(),
-                owner_id: current_account.clone(),
-                receiver_id: first.account().clone(),
-                token_name: token_id.clone(),
-                tokens_per_sec: self.tokens_per_sec.clone(),
-                is_locked: false,
-                is_auto_start_enabled: false,
-                description: "{\"player\": \"first\"}".to_string(),
-            };
-            let request_json =
-                serde_json::to_string(&request).expect("failed to serialize request");
-            let msg = format!("{{\"Create\": {{ \"request\": {request_json} }}}}");
-
-            let first_player_deposit_to_stream = token::ext(token_id.clone())
-                .with_static_gas(Gas(60 * TGAS))
-                .with_attached_deposit(1)
-                .ft_transfer_call(streaming_id.clone(), amount, memo, msg);
-
-            let first_player_get_stream_account =
-                streaming_roketo::ext(streaming_id.clone()).get_account(current_account.clone());
-
-            let first_account_id = first.account().clone();
-            let first_player_query_stream_id =
-                Self::ext(current_account.clone()).query_stream_id_callback(first_account_id);
-
-            let second = self.second_player();
-            let memo = format!("Roketo transfer: {}", second.account());
-            request.receiver_id = second.account().clone();
-            request.description = "{\"player\": \"second\"}".to_string();
-            let request_json =
-                serde_json::to_string(&request).expect("failed to serialize request");
-            let msg = format!("{{\"Create\": {{ \"request\": {request_json} }}}}");
-
-            let second_player_deposit_to_stream = token::ext(token_id)
-                .with_static_gas(Gas(60 * TGAS))
-                .with_attached_deposit(1)
-                .ft_transfer_call(streaming_id.clone(), amount, memo, msg);
-
-            let second_player_get_stream_account =
-                streaming_roketo::ext(streaming_id.clone()).get_account(current_account.clone());
-
-            let second_account_id = second.account().clone();
-            let second_player_query_stream_id =
-                Self::ext(current_account).query_stream_id_callback(second_account_id);
-
-            self.game = Some(Game::game_setup(self.num_cubes));
-
-            let promise = first_player_deposit_to_stream
-                .then(first_player_get_stream_account)
-                .then(first_player_query_stream_id)
-                .then(second_player_deposit_to_stream)
-                .then(second_player_get_stream_account)
-                .then(second_player_query_stream_id);
-            PromiseOrValue::Promise(promise)
-        } else {
-            panic!("all players are in, registration closed");
+        self.cancel_expired_registration();
+
+        let currency = Currency::Token(token_id);
+        let registered = serde_json::from_str::<TransferMessage>(&msg)
+            .map_err(|err| format!("invalid msg: {}", err))
+            .and_then(|message| match message {
+                TransferMessage::CreateGame(params) => self
+                    .open_game(sender_id, currency, amount, params)
+                    .map(|()| PromiseOrValue::Value(U128(0))),
+                TransferMessage::JoinGame { game_id } => {
+                    self.join_open_game(sender_id, currency, amount, game_id)?;
+                    Ok(self.start_registered_game())
+                }
+                TransferMessage::PostChallenge(params) => self
+                    .open_challenge(sender_id, currency, amount, params)
+                    .map(|_| PromiseOrValue::Value(U128(0))),
+                TransferMessage::AcceptChallenge { challenge_id } => Ok(
+                    match self.accept_open_challenge(sender_id, currency, amount, challenge_id)? {
+                        Some(promise) => PromiseOrValue::Promise(promise),
+                        None => PromiseOrValue::Value(U128(0)),
+                    },
+                ),
+            });
+
+        match registered {
+            Ok(promise) => promise,
+            Err(reason) => {
+                log!("transfer refused: {}", reason);
+                PromiseOrValue::Value(amount)
+            }
         }
     }
 }
@@ -1,17 +1,5 @@
-use near_sdk::serde::Serialize;
-use near_sdk::AccountId;
-
+mod ft_message;
 mod ft_receiver;
+mod native;
 
-#[derive(Serialize)]
-#[serde(crate = "near_sdk::serde")]
-struct RoketoStreamingCreateRequest {
-    balance: String,
-    owner_id: AccountId,
-    receiver_id: AccountId,
-    token_name: AccountId,
-    tokens_per_sec: String,
-    is_locked: bool,
-    is_auto_start_enabled: bool,
-    description: String,
-}
+pub(crate) use ft_message::GameParams;
@@ -0,0 +1,71 @@
+use near_sdk::{env, json_types::U128, near_bindgen, Promise};
+
+use crate::challenges::{ChallengeId, ChallengeParams};
+use crate::{currency::Currency, game::GameId, Contract, ContractExt};
+
+use super::GameParams;
+
+#[near_bindgen]
+impl Contract {
+    /// Open a game for the attached NEAR, like a `CreateGame` transfer does
+    /// for tokens. Panics, returning the deposit, if the game can't be opened.
+    #[payable]
+    pub fn create_game(&mut self, params: GameParams) {
+        self.cancel_expired_registration();
+        let deposit = U128(env::attached_deposit());
+        if let Err(reason) = self.open_game(
+            env::predecessor_account_id(),
+            Currency::Near,
+            deposit,
+            params,
+        ) {
+            env::panic_str(&reason);
+        }
+    }
+
+    /// Join the open game `game_id` with the same amount of attached NEAR.
+    /// Stakes in NEAR are kept by the contract and the players' time by its
+    /// chess clock, so the game is set up at once.
+    #[payable]
+    pub fn join_game(&mut self, game_id: GameId) {
+        let deposit = U128(env::attached_deposit());
+        if let Err(reason) = self.join_open_game(
+            env::predecessor_account_id(),
+            Currency::Near,
+            deposit,
+            game_id,
+        ) {
+            env::panic_str(&reason);
+        }
+        self.set_up_game();
+    }
+
+    /// Post a challenge for the attached NEAR, like a `PostChallenge`
+    /// transfer does for tokens.
+    #[payable]
+    pub fn post_challenge(&mut self, params: ChallengeParams) -> ChallengeId {
+        let deposit = U128(env::attached_deposit());
+        self.open_challenge(
+            env::predecessor_account_id(),
+            Currency::Near,
+            deposit,
+            params,
+        )
+        .unwrap_or_else(|reason| env::panic_str(&reason))
+    }
+
+    /// Accept the challenge `challenge_id` for NEAR, attaching its stake.
+    /// The game is set up as soon as the contract is free.
+    #[payable]
+    pub fn accept_challenge(&mut self, challenge_id: ChallengeId) -> Option<Promise> {
+        self.cancel_expired_registration();
+        let deposit = U128(env::attached_deposit());
+        self.accept_open_challenge(
+            env::predecessor_account_id(),
+            Currency::Near,
+            deposit,
+            challenge_id,
+        )
+        .unwrap_or_else(|reason| env::panic_str(&reason))
+    }
+}
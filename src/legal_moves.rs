@@ -0,0 +1,74 @@
+use crate::direction::get_roll_direction;
+use crate::face::GameCubeFace;
+use crate::game::{Game, GameCube, GamePhase};
+use crate::move_pattern::*;
+use crate::player::win_position;
+use crate::position::GameCubePosition;
+
+impl Game {
+    /// Squares the cube at `from` may legally move to in the current phase.
+    /// Mirrors the checks done by `try_make_move`.
+    pub fn legal_targets(&self, from: GameCubePosition) -> Vec<GameCubePosition> {
+        let cube = match self.get_cube_at(from) {
+            Some(cube) if cube.player == self.active_player => cube,
+            _ => return vec![],
+        };
+        let own_win = win_position(self.active_player);
+        let other_win = win_position(3 - self.active_player);
+
+        match self.phase {
+            GamePhase::Roll => self
+                .free_squares(t_move_pattern(from.x, from.y))
+                .into_iter()
+                .filter(|to| *to != other_win)
+                .filter(|to| {
+                    let dir_after = cube
+                        .direction
+                        .direction_after_roll(get_roll_direction(from, *to));
+                    if dir_after.up == 1 {
+                        *to == own_win
+                    } else {
+                        *to != own_win
+                    }
+                })
+                .collect(),
+            GamePhase::Hop => self
+                .free_squares(hop_pattern(&cube))
+                .into_iter()
+                .filter(|to| *to != other_win)
+                .collect(),
+            GamePhase::End => vec![],
+        }
+    }
+
+    /// All legal `(from, to)` moves of the active player.
+    pub fn legal_moves(&self) -> Vec<(GameCubePosition, GameCubePosition)> {
+        self.board
+            .iter()
+            .filter(|cube| cube.player == self.active_player)
+            .flat_map(|cube| {
+                self.legal_targets(cube.position)
+                    .into_iter()
+                    .map(move |to| (cube.position, to))
+            })
+            .collect()
+    }
+
+    fn free_squares(&self, pattern: Vec<(i8, i8)>) -> Vec<GameCubePosition> {
+        coord_pattern_to_positions(pattern)
+            .into_iter()
+            .filter(|p| self.get_cube_at(*p).is_none())
+            .collect()
+    }
+}
+
+fn hop_pattern(cube: &GameCube) -> Vec<(i8, i8)> {
+    let (x, y) = (cube.position.x, cube.position.y);
+    match cube.direction.up.into() {
+        GameCubeFace::THop => t_move_pattern(x, y),
+        GameCubeFace::XHop => x_move_pattern(x, y),
+        GameCubeFace::Slide => l_move_pattern(x, y),
+        GameCubeFace::Hoops => h_move_pattern(x, y),
+        _ => vec![],
+    }
+}
@@ -1,32 +1,66 @@
 #![allow(unused_imports)]
 
+pub mod ansi;
+pub mod board_view;
+mod challenges;
+pub mod chess_clock;
+pub mod clock;
+pub mod currency;
 pub mod direction;
+pub mod events;
 mod external;
 pub mod face;
 pub mod formatter;
 pub mod game;
+pub mod game_options;
 pub mod game_setup;
 mod interface;
+pub mod legal_moves;
+mod migration;
 pub mod move_pattern;
+pub mod notation;
+mod pause;
 pub mod player;
 pub mod position;
+mod profiles;
+mod registration;
+pub mod settlement;
+mod storage;
+mod stream_sync;
+pub mod svg;
+mod token_whitelist;
+mod treasury;
+mod upgrade;
 mod views;
+mod winnings;
 
 use std::collections::HashMap;
 
-use external::{
-    streaming_roketo::streaming_roketo::{self, StreamingRoketoExt},
-    token::token,
-};
+use external::token::token;
 
+use challenges::{AcceptedChallenge, Challenge, ChallengeId};
+use chess_clock::ChessClock;
+use clock::{ClockBackend, StreamStatus};
+use currency::Currency;
 use direction::GameCubeDirection;
-use game::{Game, GameCube, GamePhase};
+use events::{move_events, Event};
+use game::{Game, GameCube, GameId, GamePhase};
+use game_options::{Ruleset, Side, TimeControl};
+use interface::GameParams;
 use near_sdk::json_types::U128;
+use notation::{GameMove, MoveKind};
 use player::{GamePlayerIndex, Player};
 use position::GameCubePosition;
+use profiles::{Profile, Rank};
+use registration::{GameStatus, REGISTRATION_TIMEOUT};
+use settlement::{PayoutStatus, Settlement};
+use storage::StorageAccount;
+use token_whitelist::StakeLimits;
+use treasury::DEFAULT_FEE_BPS;
+use winnings::Winnings;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::Vector;
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, Vector};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::Value;
 use near_sdk::{
@@ -36,21 +70,65 @@ use near_sdk::{
 
 use crate::external::TGAS;
 
+#[derive(BorshStorageKey, BorshSerialize)]
+enum StorageKey {
+    Settlements,
+    Claimable,
+    Storage,
+    Profiles,
+    Challenges,
+    Ranking,
+    AcceptedChallenges,
+}
+
 /// Contract state definition.
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize, Serialize)]
-#[serde(crate = "near_sdk::serde")]
-
+#[derive(BorshDeserialize, BorshSerialize)]
 pub struct Contract {
     game: Option<Game>,
     is_finished: bool,
     first: Option<Player>,
     second: Option<Player>,
-    token_id: Option<AccountId>,
+    currency: Option<Currency>,
     deposit: u128,
     tokens_per_sec: String,
     streaming_id: Option<AccountId>,
     num_cubes: Option<usize>,
+    status: GameStatus,
+    registration_deadline: Option<u64>,
+    game_id: GameId,
+    ruleset: Ruleset,
+    time_control: TimeControl,
+    /// Whether the players' stakes are streamed by roke.to, otherwise
+    /// their time is kept by `chess_clock`.
+    streamed: bool,
+    streams_started: bool,
+    chess_clock: Option<ChessClock>,
+    owner_id: AccountId,
+    tokens: HashMap<AccountId, StakeLimits>,
+    near_stakes: Option<StakeLimits>,
+    fee_bps: u16,
+    treasury: HashMap<Currency, u128>,
+    settlements: LookupMap<GameId, Settlement>,
+    claimable: LookupMap<AccountId, Winnings>,
+    storage: LookupMap<AccountId, StorageAccount>,
+    /// Play and registrations are halted by the owner.
+    paused: bool,
+    profiles: LookupMap<AccountId, Profile>,
+    /// Players ordered for the leaderboard.
+    ranking: TreeMap<Rank, ()>,
+    challenges: UnorderedMap<ChallengeId, Challenge>,
+    challenge_id: ChallengeId,
+    /// Accepted challenges waiting for the game in progress to be over.
+    accepted: TreeMap<ChallengeId, AcceptedChallenge>,
+    /// The only account allowed to join the open game.
+    opponent: Option<AccountId>,
+    /// Side the creator of the game asked for.
+    side: Side,
+    creator: Option<AccountId>,
+    /// Fee taken from this game's payout, fixed when its first player
+    /// registers.
+    game_fee_bps: Option<u16>,
 }
 
 /// Contract functions implementations.
@@ -58,8 +136,46 @@ pub struct Contract {
 impl Contract {
     #[init]
     #[private]
-    pub fn new(num_cubes: Option<usize>) -> Self {
+    pub fn new(num_cubes: Option<usize>, owner_id: Option<AccountId>) -> Self {
         assert!(!env::state_exists(), "Already initialized");
+        let owner_id = owner_id.unwrap_or_else(env::current_account_id);
+        migration::write_state_version();
+        Self::initial(num_cubes, owner_id)
+    }
+
+    /// Clear the finished game to open the next one, starting the oldest
+    /// accepted challenge if any. Stakes must not be held for the players:
+    /// nobody is registered, or the game is settled.
+    pub fn reset(&mut self, num_cubes: Option<usize>) -> Option<Promise> {
+        require!(
+            self.first.is_none() || self.settlements.contains_key(&self.game_id),
+            format!(
+                "game {} holds the players' stakes, it can't be reset until settled",
+                self.game_id
+            )
+        );
+        let initial = Self::initial(num_cubes, self.owner_id.clone());
+        let previous = std::mem::replace(self, initial);
+        // keep numbering games, so that stale JoinGame messages don't match
+        self.game_id = previous.game_id;
+        self.streaming_id = previous.streaming_id;
+        self.tokens = previous.tokens;
+        self.near_stakes = previous.near_stakes;
+        self.fee_bps = previous.fee_bps;
+        self.treasury = previous.treasury;
+        self.settlements = previous.settlements;
+        self.claimable = previous.claimable;
+        self.storage = previous.storage;
+        self.paused = previous.paused;
+        self.profiles = previous.profiles;
+        self.ranking = previous.ranking;
+        self.challenges = previous.challenges;
+        self.challenge_id = previous.challenge_id;
+        self.accepted = previous.accepted;
+        self.start_accepted_challenge()
+    }
+
+    fn initial(num_cubes: Option<usize>, owner_id: AccountId) -> Self {
         Self {
             game: None,
             is_finished: false,
@@ -67,24 +183,43 @@ impl Contract {
             second: None,
             deposit: 0,
             streaming_id: None,
-            token_id: None,
+            currency: None,
             tokens_per_sec: String::new(),
             num_cubes,
+            status: GameStatus::Registration,
+            registration_deadline: None,
+            game_id: 0,
+            ruleset: Ruleset::default(),
+            time_control: TimeControl::default(),
+            streamed: false,
+            streams_started: false,
+            chess_clock: None,
+            owner_id,
+            tokens: HashMap::new(),
+            near_stakes: None,
+            fee_bps: DEFAULT_FEE_BPS,
+            treasury: HashMap::new(),
+            settlements: LookupMap::new(StorageKey::Settlements),
+            claimable: LookupMap::new(StorageKey::Claimable),
+            storage: LookupMap::new(StorageKey::Storage),
+            paused: false,
+            profiles: LookupMap::new(StorageKey::Profiles),
+            ranking: TreeMap::new(StorageKey::Ranking),
+            challenges: UnorderedMap::new(StorageKey::Challenges),
+            challenge_id: 0,
+            accepted: TreeMap::new(StorageKey::AcceptedChallenges),
+            opponent: None,
+            side: Side::First,
+            creator: None,
+            game_fee_bps: None,
         }
     }
 
-    pub fn reset(&mut self, num_cubes: Option<usize>) {
-        *self = Self {
-            game: None,
-            is_finished: false,
-            first: None,
-            second: None,
-            deposit: 0,
-            token_id: None,
-            streaming_id: None,
-            tokens_per_sec: String::new(),
-            num_cubes,
-        };
+    fn assert_owner(&self) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "only the owner can call this method"
+        );
     }
 
     fn streaming_id(&self) -> &AccountId {
@@ -93,6 +228,11 @@ impl Contract {
             .expect("streaming id should be connected by now")
     }
 
+    /// Backend running the players' streams.
+    pub(crate) fn clock_backend(&self) -> clock::Backend {
+        clock::Backend::connect(self.streaming_id().clone())
+    }
+
     fn first_player(&self) -> &Player {
         self.first.as_ref().expect("first player is not registered")
     }
@@ -103,7 +243,9 @@ impl Contract {
             .expect("second player is not registered")
     }
 
+    /// Connect the roke.to contract streaming the players' stakes. Owner only.
     pub fn connect_streaming_contract(&mut self, streaming_id: AccountId) {
+        self.assert_owner();
         assert!(
             self.streaming_id.is_none(),
             "streaming contract is already connected"
@@ -114,53 +256,70 @@ impl Contract {
     fn register_first_player(
         &mut self,
         account: AccountId,
-        token_id: AccountId,
+        currency: Currency,
         deposit: U128,
-        tokens_per_sec: String,
-    ) -> PromiseOrValue<U128> {
-        assert!(self.token_id.is_none(), "somehow token ID is already set");
-
-        log!("game token set to: {}", token_id);
-        self.token_id = Some(token_id);
+        params: GameParams,
+    ) {
+        assert!(self.currency.is_none(), "somehow currency is already set");
 
-        log!("deposit set to: {}", deposit.0);
+        self.game_id += 1;
+        log!("game {} created", self.game_id);
+        self.side = params.side.unwrap_or_default();
+        // a random side is only drawn when the opponent joins
+        Event::PlayerRegistered {
+            account_id: account.clone(),
+            player: if self.side == Side::Second { 2 } else { 1 },
+            token_id: currency.token_id().cloned(),
+            amount: deposit,
+        }
+        .emit();
+        self.currency = Some(currency);
         self.deposit = deposit.0;
 
-        log!("tokens streaming rate set to: {}/sec", tokens_per_sec);
-        self.tokens_per_sec = tokens_per_sec;
+        log!(
+            "tokens streaming rate set to: {}/sec",
+            params.tokens_per_sec.0
+        );
+        self.tokens_per_sec = params.tokens_per_sec.0.to_string();
+        if params.num_cubes.is_some() {
+            self.num_cubes = params.num_cubes;
+        }
+        self.ruleset = params.ruleset.unwrap_or_default();
+        self.time_control = params.time_control.unwrap_or_default();
+        self.opponent = params.opponent;
+        self.creator = Some(account.clone());
+        self.game_fee_bps = Some(self.fee_bps);
 
-        log!("first player registered: {} ", account,);
         self.first = Some(Player::new(account, deposit, 1));
-        PromiseOrValue::Value(U128::from(0))
+        self.status = GameStatus::Registration;
+        self.registration_deadline = Some(env::block_timestamp() + REGISTRATION_TIMEOUT);
     }
 
-    pub fn start(&mut self) -> Promise {
-        let first_player_stream = self
-            .first_player()
-            .stream()
-            .expect("first players stream was not registered")
-            .clone();
-        let second_player_stream = self
-            .second_player()
-            .stream()
-            .expect("second players stream was not registered")
-            .clone();
-
-        let streaming_id = self.streaming_id().clone();
+    fn currency(&self) -> &Currency {
+        self.currency
+            .as_ref()
+            .expect("somehow currency is NOT set yet")
+    }
 
-        log!(
-            "first stream: {}, second: {}",
-            first_player_stream,
-            second_player_stream
+    /// Start the players' streams. Games for native NEAR are not streamed,
+    /// they need no start.
+    pub fn start(&mut self) -> Option<Promise> {
+        self.assert_not_paused();
+        require!(
+            self.status == GameStatus::Ready,
+            "players' streams are not created yet"
         );
-        let start_first_player_stream =
-            start_stream(streaming_id.clone(), first_player_stream.clone());
-        let pause_first_player_stream = pause_stream(streaming_id.clone(), first_player_stream);
-        let start_second_player_stream = start_stream(streaming_id, second_player_stream);
+        if !self.streamed {
+            return None;
+        }
+        require!(!self.streams_started, "streams are already started");
+        self.streams_started = true;
 
-        start_first_player_stream
-            .then(pause_first_player_stream)
-            .then(start_second_player_stream)
+        let first = self.first_player().clone();
+        let second = self.second_player().clone();
+        let promise = self.stream_action(None, &first, StreamStatus::Active);
+        let promise = self.stream_action(Some(promise), &first, StreamStatus::Paused);
+        Some(self.stream_action(Some(promise), &second, StreamStatus::Active))
     }
 
     pub fn game_state(&self) -> Option<Game> {
@@ -180,7 +339,6 @@ impl Contract {
         match pos {
             Some(pos) => {
                 let cube = game.as_ref().unwrap().get_cube_at(pos);
-                if cube.is_some() {}
                 match cube {
                     Some(..) => {
                         log!("{}", cube.unwrap().direction.format_layout());
@@ -200,6 +358,7 @@ impl Contract {
     }
 
     pub fn pass_move(&mut self) -> Option<Promise> {
+        self.assert_not_paused();
         require!(self.game.is_some(), "Game is not started!");
         let mut game = self.game.as_ref().unwrap().clone();
 
@@ -209,30 +368,32 @@ impl Contract {
                 || (current == *self.second.as_ref().unwrap().account() && game.active_player == 2),
             "Wrong player's turn! "
         );
+        if self.is_flagged() {
+            return self.time_out();
+        }
 
-        let phase = game.phase;
-        let active_player_ind = game.active_player;
-        let other_player_ind = 3 - active_player_ind;
-
-        match phase {
-            GamePhase::End => {
+        match game.pass_turn() {
+            None => {
                 log!("Game is finished, no moves allowed");
                 None
             }
-            _ => {
-                game.phase = GamePhase::Roll;
-                game.active_player = other_player_ind;
+            Some(report) => {
+                let other_player_ind = game.active_player;
                 self.game = Some(game);
-                log!(
-                    "Player {} passed the turn. It is player {} Roll phase.",
-                    active_player_ind,
-                    other_player_ind
-                );
+                log!("{}", report);
+                Event::Pass {
+                    player: 3 - other_player_ind,
+                }
+                .emit();
                 match other_player_ind {
                     // 1 => Some(self.check_winner(self.first_player(), self.second_player())),
                     // 2 => Some(self.check_winner(self.second_player(), self.first_player())),
-                    1 => Some(self.check_winner(self.second_player(), self.first_player())),
-                    2 => Some(self.check_winner(self.first_player(), self.second_player())),
+                    1 => {
+                        self.check_winner(self.second_player().clone(), self.first_player().clone())
+                    }
+                    2 => {
+                        self.check_winner(self.first_player().clone(), self.second_player().clone())
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -240,8 +401,13 @@ impl Contract {
     }
 
     pub fn make_move(&mut self, from_x: i8, from_y: i8, to_x: i8, to_y: i8) -> Option<Promise> {
+        self.assert_not_paused();
         require!(self.game.is_some(), "Game is not started!");
         let mut game = self.game.as_ref().unwrap().clone();
+        require!(
+            game.phase != GamePhase::End,
+            "Game is finished, no moves allowed"
+        );
 
         let current = env::signer_account_id();
         let active_before = game.active_player;
@@ -250,6 +416,9 @@ impl Contract {
                 || (current == *self.second.as_ref().unwrap().account() && game.active_player == 2),
             "Wrong player's turn! "
         );
+        if self.is_flagged() {
+            return self.time_out();
+        }
 
         let from = GameCubePosition::from(from_x, from_y);
         assert!(
@@ -269,10 +438,13 @@ impl Contract {
         let from = from.unwrap();
         let to = to.unwrap();
 
+        let before = game.clone();
         let move_report = game.try_make_move(from, to);
 
         log!("{}", move_report);
-        log!("{}", game.format_board());
+        for event in move_events(&before, &game, from, to) {
+            event.emit();
+        }
 
         let active_after = game.active_player;
         //NOTE: as a result of the move a game can end!
@@ -286,8 +458,8 @@ impl Contract {
             match active_after {
                 // 1 => Some(self.check_winner(self.first_player(), self.second_player())),
                 // 2 => Some(self.check_winner(self.second_player(), self.first_player())),
-                1 => Some(self.check_winner(self.second_player(), self.first_player())),
-                2 => Some(self.check_winner(self.first_player(), self.second_player())),
+                1 => self.check_winner(self.second_player().clone(), self.first_player().clone()),
+                2 => self.check_winner(self.first_player().clone(), self.second_player().clone()),
                 _ => unreachable!(),
             }
         } else {
@@ -295,117 +467,1300 @@ impl Contract {
         }
     }
 
-    fn check_winner(&self, active: &Player, passive: &Player) -> Promise {
+    /// Same as `make_move`, with the move given in algebraic notation:
+    /// `c7-c6` for a Roll, `c6xe4` for a Hop.
+    pub fn play(&mut self, mv: String) -> Option<Promise> {
+        require!(self.game.is_some(), "Game is not started!");
+        let game_move = GameMove::parse(&mv);
+        assert!(
+            game_move.is_some(),
+            "Cannot parse move '{}'. Expected e.g. 'c7-c6' (Roll) or 'c6xe4' (Hop)",
+            mv
+        );
+        let game_move = game_move.unwrap();
+
+        let phase = self.game.as_ref().unwrap().phase;
+        if let Some(kind) = MoveKind::for_phase(phase) {
+            assert!(
+                kind == game_move.kind,
+                "Move '{}' is a {:?}, but it is {:?} phase",
+                mv,
+                game_move.kind,
+                phase
+            );
+        }
+
+        let (from, to) = (game_move.from, game_move.to);
+        self.make_move(from.x, from.y, to.x, to.y)
+    }
+
+    /// Whether the active player has run out of time on the contract's clock.
+    fn is_flagged(&self) -> bool {
+        let game = self.game.as_ref().expect("Game is not started!");
+        game.phase != GamePhase::End
+            && self
+                .chess_clock
+                .as_ref()
+                .and_then(|clock| clock.flagged(env::block_timestamp()))
+                .is_some()
+    }
+
+    /// The active player loses on time.
+    fn time_out(&mut self) -> Option<Promise> {
+        let mut game = self.game.clone().unwrap();
+        let loser = game.active_player;
+        log!("{}", game.forfeit(loser));
+        Event::FlagFall { player: loser }.emit();
+        self.game = Some(game);
+        self.is_finished = true;
+        match loser {
+            1 => self.check_winner(self.first_player().clone(), self.second_player().clone()),
+            2 => self.check_winner(self.second_player().clone(), self.first_player().clone()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// End the game whose active player ran out of time, paying out the
+    /// opponent. Anyone can call it.
+    pub fn claim_timeout(&mut self) -> Option<Promise> {
+        self.assert_not_paused();
+        require!(self.game.is_some(), "Game is not started!");
+        require!(
+            self.is_flagged(),
+            "the active player still has time on the clock"
+        );
+        self.time_out()
+    }
+
+    fn check_winner(&mut self, active: Player, passive: Player) -> Option<Promise> {
         require!(self.game.is_some(), "Game is not started!");
         let game = self.game.as_ref().unwrap().clone();
-        let streaming_id = self.streaming_id.as_ref().unwrap();
+        let streamed = self.streamed;
         let current_id = env::current_account_id();
 
         match game.phase {
             GamePhase::End => {
-                log!("player {} WON!", active.account());
-                let promise = stop_stream(streaming_id.clone(), active.stream().unwrap().clone());
-                let promise = promise.then(stop_stream(
-                    streaming_id.clone(),
-                    passive.stream().unwrap().clone(),
-                ));
-                let promise = promise.then(get_stream(
-                    streaming_id.clone(),
-                    active.stream().unwrap().clone(),
-                ));
-                let promise = promise.then(
-                    Self::ext(current_id)
-                        .query_transferred_tokens_callback(active.account().clone()),
-                );
-                promise
+                // The game ends on the winner's move, so the winner is still
+                // the active player: the one whose clock is `passive` here.
+                let (winner, loser) = (passive, active);
+                Event::GameOver {
+                    winner: winner.account().clone(),
+                    player: game.active_player,
+                }
+                .emit();
+                if !streamed {
+                    self.chess_clock
+                        .as_mut()
+                        .unwrap()
+                        .stop(env::block_timestamp());
+                    // nothing was streamed, both stakes are still in escrow
+                    return self.pay_out(winner.account().clone(), 0, 0);
+                }
+                let promise = self.stream_action(None, &winner, StreamStatus::Finished);
+                let promise = self.stream_action(Some(promise), &loser, StreamStatus::Finished);
+                let promise = promise
+                    .then(Self::ext(current_id).query_streams_callback(winner.account().clone()));
+                Some(promise)
             }
 
-            _ => pause_stream(streaming_id.clone(), passive.stream().unwrap().clone()).then(
-                start_stream(streaming_id.clone(), active.stream().unwrap().clone()),
-            ),
+            _ if !streamed => {
+                self.chess_clock
+                    .as_mut()
+                    .unwrap()
+                    .switch(game.active_player, env::block_timestamp());
+                None
+            }
+
+            _ => {
+                let promise = self.stream_action(None, &passive, StreamStatus::Paused);
+                Some(self.stream_action(Some(promise), &active, StreamStatus::Active))
+            }
         }
     }
 
+    /// Settle the finished streamed game whose settlement failed, once
+    /// `sync_streams` has confirmed both streams are stopped. Anyone can call it.
+    pub fn settle(&mut self) -> Promise {
+        let game = self.game.as_ref().expect("Game is not started!");
+        require!(game.phase == GamePhase::End, "game is not over yet");
+        require!(
+            !self.settlements.contains_key(&self.game_id),
+            "game is already settled"
+        );
+        let winner = match game.active_player {
+            1 => self.first_player(),
+            _ => self.second_player(),
+        };
+        let promise = self.query_final_streams(winner.account().clone());
+        require!(
+            promise.is_some(),
+            "streams are not stopped yet, call sync_streams"
+        );
+        promise.unwrap()
+    }
+
+    /// Reads the final states of both streams once they are stopped.
+    #[private]
+    pub fn query_streams_callback(&self, player_id: AccountId) -> Option<Promise> {
+        let promise = self.query_final_streams(player_id);
+        if promise.is_none() {
+            log!(
+                "streams of game {} are not stopped, call sync_streams and settle",
+                self.game_id
+            );
+        }
+        promise
+    }
+
+    /// Settles the game from the final states of the stopped streams. The
+    /// game stays unsettled if they can't be read, until `settle` is called.
     #[private]
     pub fn query_transferred_tokens_callback(
         &mut self,
-        #[callback_result] call_result: Result<HashMap<String, Value>, PromiseError>,
+        #[callback_result] winner_stream: Result<HashMap<String, Value>, PromiseError>,
+        #[callback_result] loser_stream: Result<HashMap<String, Value>, PromiseError>,
         player_id: AccountId,
-    ) -> Promise {
-        let res = call_result.unwrap();
-        log!("res: {:?}", res);
-        let withdrawn: u128 = res
-            .get("tokens_total_withdrawn")
-            .and_then(|v| v.as_str())
-            .expect("unexpected response from roke.to contract")
-            .parse()
-            .expect("couldn't parse tokens amount in roke.to response");
-        let win_money = if self.first_player().account() == &player_id {
-            self.first_player().deposit().0
-        } else if self.second_player().account() == &player_id {
-            self.second_player().deposit().0
+    ) -> Option<Promise> {
+        match (
+            tokens_withdrawn(winner_stream),
+            tokens_withdrawn(loser_stream),
+        ) {
+            (Ok(winner_streamed), Ok(loser_streamed)) => {
+                self.pay_out(player_id, winner_streamed, loser_streamed)
+            }
+            (Err(reason), _) | (_, Err(reason)) => {
+                log!("game {} is not settled: {}", self.game_id, reason);
+                None
+            }
+        }
+    }
+
+    /// Query both streams and settle, if both are confirmed to be stopped.
+    fn query_final_streams(&self, winner_id: AccountId) -> Option<Promise> {
+        let (winner, loser) = self.winner_and_loser(&winner_id);
+        let stopped = |player: &Player| player.stream_status == Some(StreamStatus::Finished);
+        if !stopped(winner) || !stopped(loser) {
+            return None;
+        }
+        let clock = self.clock_backend();
+        // a joint promise can't be a callback itself, so it is made here
+        let promise = clock
+            .get_stream(winner.stream().unwrap().clone())
+            .and(clock.get_stream(loser.stream().unwrap().clone()))
+            .then(
+                Self::ext(env::current_account_id()).query_transferred_tokens_callback(winner_id),
+            );
+        Some(promise)
+    }
+
+    fn winner_and_loser(&self, winner_id: &AccountId) -> (&Player, &Player) {
+        if self.first_player().account() == winner_id {
+            (self.first_player(), self.second_player())
+        } else if self.second_player().account() == winner_id {
+            (self.second_player(), self.first_player())
         } else {
             unreachable!();
-        } - withdrawn;
-        let win_money = win_money / 100 * 90;
-        log!("reward {} tokens (90%) to {}", win_money, player_id);
-        token::ext(self.token_id.as_ref().unwrap().clone())
-            .with_attached_deposit(1)
-            .ft_transfer_call(
-                player_id,
-                U128::from(win_money),
-                String::new(),
-                String::new(),
-            )
+        }
     }
 
-    #[private]
-    pub fn query_stream_id_callback(
+    /// Settle the game won by `player_id`, once the players' streams paid
+    /// them back `winner_streamed` and `loser_streamed` of their stakes.
+    /// A game is settled only once.
+    fn pay_out(
         &mut self,
-        #[callback_result] call_result: Result<HashMap<String, Value>, PromiseError>,
         player_id: AccountId,
-    ) -> U128 {
-        let res = call_result.unwrap();
-        let id = res.get("last_created_stream").unwrap().as_str().unwrap();
-        log!("[{}] stream id: {}", player_id, id);
-
-        let first = self.first.as_mut().unwrap();
-        let second = self.second.as_mut().unwrap();
-        if first.account() == &player_id {
-            first.stream = Some(id.to_string());
-        } else if second.account() == &player_id {
-            second.stream = Some(id.to_string());
-        } else {
-            panic!("unknown player ID");
+        winner_streamed: u128,
+        loser_streamed: u128,
+    ) -> Option<Promise> {
+        require!(
+            !self.settlements.contains_key(&self.game_id),
+            format!("game {} is already settled", self.game_id)
+        );
+        let (winner, loser) = self.winner_and_loser(&player_id);
+        let loser_id = loser.account().clone();
+        let settlement = Settlement::compute(
+            self.game_id,
+            self.currency().clone(),
+            self.game_fee_bps
+                .expect("fee is fixed when the first player registers"),
+            (winner, winner_streamed),
+            (loser, loser_streamed),
+        );
+        let win_money = settlement.winner.payout.0;
+        log!(
+            "reward {} {} to {}, fee {}",
+            win_money,
+            self.currency(),
+            player_id,
+            settlement.fee.0
+        );
+        self.accrue_fee(settlement.fee.0);
+        self.settlements.insert(&self.game_id, &settlement);
+        self.record_result(&player_id, &loser_id);
+        if settlement.payout_status == PayoutStatus::Completed {
+            return None;
         }
-        U128(0)
+        Event::Payout {
+            account_id: player_id.clone(),
+            token_id: self.currency().token_id().cloned(),
+            amount: U128::from(win_money),
+        }
+        .emit();
+        Some(self.send_winnings(
+            player_id,
+            self.currency().clone(),
+            win_money,
+            Some(self.game_id),
+        ))
     }
 }
 
-fn streaming(streaming_id: AccountId) -> StreamingRoketoExt {
-    streaming_roketo::ext(streaming_id)
-        .with_attached_deposit(1)
-        .with_static_gas(Gas(60 * TGAS))
+/// Tokens a stopped stream paid to its receiver.
+fn tokens_withdrawn(stream: Result<HashMap<String, Value>, PromiseError>) -> Result<u128, String> {
+    let stream = stream.map_err(|_| "couldn't get the stream from roke.to".to_string())?;
+    log!("stream: {:?}", stream);
+    let status = stream.get("status").and_then(StreamStatus::from_json);
+    if status != Some(StreamStatus::Finished) {
+        return Err(format!("stream is {:?}, not finished", status));
+    }
+    stream
+        .get("tokens_total_withdrawn")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "unexpected response from roke.to contract".to_string())?
+        .parse()
+        .map_err(|_| "couldn't parse tokens amount in roke.to response".to_string())
 }
 
-fn start_stream(streaming_id: AccountId, stream_id: String) -> Promise {
-    streaming(streaming_id).start_stream(stream_id)
+impl Default for Contract {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
 }
 
-fn pause_stream(streaming_id: AccountId, stream_id: String) -> Promise {
-    streaming(streaming_id).pause_stream(stream_id)
-}
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::mock::VmAction;
+    use near_sdk::serde_json::json;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
 
-fn stop_stream(streaming_id: AccountId, stream_id: String) -> Promise {
-    streaming(streaming_id).stop_stream(stream_id)
-}
+    use super::*;
+    use crate::clock::mock::MockClock;
+    use crate::storage::{storage_cost, ACCOUNT_STORAGE_BYTES, GAME_STORAGE_BYTES};
 
-fn get_stream(streaming_id: AccountId, stream_id: String) -> Promise {
-    streaming(streaming_id).get_stream(stream_id)
-}
+    const STAKE: u128 = 1_000;
 
-impl Default for Contract {
-    fn default() -> Self {
-        Self::new(None)
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    /// Calls from `predecessor` at `seconds` since the test start.
+    fn call_from(predecessor: &str, seconds: u64) {
+        pay_from(predecessor, seconds, 0);
+    }
+
+    fn pay_from(predecessor: &str, seconds: u64, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account("game.near"))
+            .predecessor_account_id(account(predecessor))
+            .signer_account_id(account(predecessor))
+            .block_timestamp(seconds * 1_000_000_000)
+            .attached_deposit(deposit)
+            .build());
+    }
+
+    /// Callback of the contract, after a promise that `succeeded` or failed.
+    fn callback(succeeded: bool) {
+        let result = if succeeded {
+            near_sdk::PromiseResult::Successful(vec![])
+        } else {
+            near_sdk::PromiseResult::Failed
+        };
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account("game.near"))
+                .predecessor_account_id(account("game.near"))
+                .build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    fn count_events(event: &str) -> usize {
+        let tag = format!("\"event\":\"{}\"", event);
+        get_logs().iter().filter(|log| log.contains(&tag)).count()
+    }
+
+    /// The player registered last was seated as player `index`.
+    fn registered_as(index: GamePlayerIndex) -> bool {
+        let seat = format!("\"player\":{}", index);
+        get_logs()
+            .iter()
+            .any(|log| log.contains("player_registered") && log.contains(&seat))
+    }
+
+    /// `player` covered the storage of the games they open.
+    fn storage_deposited(contract: &mut Contract, player: &str) {
+        pay_from(
+            player,
+            0,
+            storage_cost(ACCOUNT_STORAGE_BYTES + GAME_STORAGE_BYTES),
+        );
+        contract.storage_deposit(None, None);
+    }
+
+    /// Both players deposited their stakes in wrap.near.
+    fn joined() -> Contract {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        contract.connect_streaming_contract(account("streaming.near"));
+        storage_deposited(&mut contract, "alice.near");
+
+        call_from("wrap.near", 0);
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+        contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.to_string());
+        let join = r#"{"JoinGame": {"game_id": 1}}"#;
+        contract.ft_on_transfer(account("bob.near"), U128(STAKE), join.to_string());
+        assert_eq!(contract.status, GameStatus::CreatingStreams);
+        contract
+    }
+
+    /// Both players staked native NEAR, with a minute on the clock and
+    /// a 5 seconds increment.
+    fn native_joined() -> Contract {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.set_near_stakes(Some(StakeLimits {
+            min_stake: U128(1),
+            max_stake: U128(STAKE),
+        }));
+        storage_deposited(&mut contract, "alice.near");
+        pay_from("alice.near", 0, STAKE);
+        let params = r#"{"tokens_per_sec": "1", "time_control": {"seconds": 60, "increment": 5}}"#;
+        contract.create_game(near_sdk::serde_json::from_str(params).unwrap());
+        pay_from("bob.near", 0, STAKE);
+        contract.join_game(1);
+        contract
+    }
+
+    /// roke.to took `player`'s stake into the stream created last.
+    fn stream_created(contract: &mut Contract, clock: &MockClock, player: &str) -> String {
+        let stream_id = clock.last_created_stream();
+        call_from("game.near", 0);
+        contract.on_stream_deposit(Ok(U128(STAKE)), account(player));
+        contract.query_stream_id_callback(Ok(clock.account_json()), account(player));
+        stream_id
+    }
+
+    /// Play the active player's moves until the turn passes to the opponent.
+    fn play_turn(contract: &mut Contract, player: &str, seconds: u64) {
+        call_from(player, seconds);
+        let active = contract.game.as_ref().unwrap().active_player;
+        while contract.game.as_ref().unwrap().active_player == active {
+            let moves = contract.game.as_ref().unwrap().legal_moves();
+            match moves.first() {
+                Some((from, to)) => contract.make_move(from.x, from.y, to.x, to.y),
+                None => contract.pass_move(),
+            };
+        }
+    }
+
+    #[test]
+    fn test_turns_switch_streams() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        let alice_stream = stream_created(&mut contract, &clock, "alice.near");
+        let bob_stream = stream_created(&mut contract, &clock, "bob.near");
+        assert_eq!(contract.status, GameStatus::Ready);
+        let stream = clock.stream(&bob_stream);
+        assert_eq!(stream.receiver_id, account("bob.near"));
+        assert_eq!((stream.balance, stream.tokens_per_sec), (STAKE, 1));
+
+        contract.start();
+        // alice thinks: bob's stream pays bob back
+        assert_eq!(clock.stream(&alice_stream).status, StreamStatus::Paused);
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Active);
+
+        play_turn(&mut contract, "alice.near", 10);
+        assert_eq!(clock.stream(&alice_stream).status, StreamStatus::Active);
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Paused);
+        assert_eq!(clock.stream(&bob_stream).withdrawn, 10);
+
+        play_turn(&mut contract, "bob.near", 15);
+        assert_eq!(clock.stream(&alice_stream).status, StreamStatus::Paused);
+        assert_eq!(clock.stream(&alice_stream).withdrawn, 5);
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Active);
+    }
+
+    #[test]
+    fn test_sync_repairs_failed_stream_action() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        stream_created(&mut contract, &clock, "alice.near");
+        let bob_stream = stream_created(&mut contract, &clock, "bob.near");
+        contract.start();
+        // nothing is confirmed yet
+        assert_eq!(contract.stream_mismatches().len(), 2);
+        for (player, status) in [
+            ("alice.near", StreamStatus::Active),
+            ("alice.near", StreamStatus::Paused),
+            ("bob.near", StreamStatus::Active),
+        ] {
+            callback(true);
+            contract.on_stream_action(account(player), status);
+        }
+        assert!(contract.stream_mismatches().is_empty());
+
+        // bob's stream fails to pause when alice's turn ends
+        play_turn(&mut contract, "alice.near", 10);
+        clock.start_stream(bob_stream.clone());
+        callback(false);
+        contract.on_stream_action(account("bob.near"), StreamStatus::Paused);
+        callback(true);
+        contract.on_stream_action(account("alice.near"), StreamStatus::Active);
+        let mismatches = contract.stream_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].account_id, account("bob.near"));
+        assert_eq!(mismatches[0].expected, StreamStatus::Paused);
+        assert_eq!(mismatches[0].confirmed, Some(StreamStatus::Active));
+
+        call_from("game.near", 20);
+        contract.on_streams_queried(
+            Ok(clock.stream_json("stream-1")),
+            Ok(clock.stream_json(&bob_stream)),
+        );
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Paused);
+        callback(true);
+        contract.on_stream_action(account("bob.near"), StreamStatus::Paused);
+        assert!(contract.stream_mismatches().is_empty());
+    }
+
+    #[test]
+    fn test_game_over_settles_both_streams() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        let alice_stream = stream_created(&mut contract, &clock, "alice.near");
+        let bob_stream = stream_created(&mut contract, &clock, "bob.near");
+        contract.start();
+
+        // alice wins after thinking for 100 seconds
+        call_from("alice.near", 100);
+        contract.game.as_mut().unwrap().phase = GamePhase::End;
+        let (alice, bob) = (
+            contract.first_player().clone(),
+            contract.second_player().clone(),
+        );
+        contract.check_winner(bob, alice);
+        assert_eq!(clock.stream(&alice_stream).status, StreamStatus::Finished);
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Finished);
+        for player in ["alice.near", "bob.near"] {
+            callback(true);
+            contract.on_stream_action(account(player), StreamStatus::Finished);
+        }
+
+        call_from("game.near", 100);
+        assert!(contract
+            .query_streams_callback(account("alice.near"))
+            .is_some());
+        contract.query_transferred_tokens_callback(
+            Ok(clock.stream_json(&alice_stream)),
+            Ok(clock.stream_json(&bob_stream)),
+            account("alice.near"),
+        );
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.winner.account_id, account("alice.near"));
+        assert_eq!(settlement.loser.streamed, U128(100));
+        // 10% of the 1900 left in the streams
+        assert_eq!(settlement.fee, U128(190));
+        assert_eq!(settlement.winner.payout, U128(1_710));
+        assert_eq!(contract.fees().accrued[0].amount, U128(190));
+        assert_eq!(count_events("payout"), 1);
+        assert_eq!(settlement.payout_status, PayoutStatus::Pending);
+
+        // alice isn't registered with wrap.near, the payout bounces
+        let wrap = Currency::Token(account("wrap.near"));
+        callback(false);
+        contract.on_payout(account("alice.near"), wrap, U128(1_710), Some(1));
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.payout_status, PayoutStatus::Claimable);
+        let claimable = contract.claimable(account("alice.near"));
+        assert_eq!(claimable[0].token_id, Some(account("wrap.near")));
+        assert_eq!(claimable[0].amount, U128(1_710));
+
+        call_from("alice.near", 200);
+        contract.claim_winnings();
+        assert!(contract.claimable(account("alice.near")).is_empty());
+    }
+
+    #[test]
+    fn test_settlement_waits_for_stopped_streams() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        let alice_stream = stream_created(&mut contract, &clock, "alice.near");
+        let bob_stream = stream_created(&mut contract, &clock, "bob.near");
+        contract.start();
+
+        call_from("alice.near", 100);
+        contract.game.as_mut().unwrap().phase = GamePhase::End;
+        let (alice, bob) = (
+            contract.first_player().clone(),
+            contract.second_player().clone(),
+        );
+        contract.check_winner(bob, alice);
+        // bob's stream fails to stop and keeps paying him
+        clock.set_status(&bob_stream, StreamStatus::Active);
+        callback(true);
+        contract.on_stream_action(account("alice.near"), StreamStatus::Finished);
+        callback(false);
+        contract.on_stream_action(account("bob.near"), StreamStatus::Finished);
+
+        call_from("game.near", 100);
+        assert!(contract
+            .query_streams_callback(account("alice.near"))
+            .is_none());
+        // nor is a live stream settled, whatever the contract believes
+        assert!(contract
+            .query_transferred_tokens_callback(
+                Ok(clock.stream_json(&alice_stream)),
+                Ok(clock.stream_json(&bob_stream)),
+                account("alice.near"),
+            )
+            .is_none());
+        assert!(contract.settlement(1).is_none());
+        assert!(contract.profile(account("alice.near")).is_none());
+
+        call_from("carol.near", 110);
+        contract.sync_streams();
+        call_from("game.near", 110);
+        contract.on_streams_queried(
+            Ok(clock.stream_json(&alice_stream)),
+            Ok(clock.stream_json(&bob_stream)),
+        );
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Finished);
+        callback(true);
+        contract.on_stream_action(account("bob.near"), StreamStatus::Finished);
+
+        call_from("carol.near", 120);
+        contract.settle();
+        call_from("game.near", 120);
+        contract.query_transferred_tokens_callback(
+            Ok(clock.stream_json(&alice_stream)),
+            Ok(clock.stream_json(&bob_stream)),
+            account("alice.near"),
+        );
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.winner.account_id, account("alice.near"));
+        // bob's stream paid him for the extra 10 seconds
+        assert_eq!(settlement.loser.streamed, U128(110));
+        // the game is rated once, when it is settled
+        let alice = contract.profile(account("alice.near")).unwrap();
+        assert_eq!((alice.games_played, alice.won), (1, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "streams are not stopped yet")]
+    fn test_no_settlement_of_live_streams() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        stream_created(&mut contract, &clock, "alice.near");
+        stream_created(&mut contract, &clock, "bob.near");
+        contract.start();
+        call_from("alice.near", 100);
+        contract.game.as_mut().unwrap().phase = GamePhase::End;
+        contract.settle();
+    }
+
+    #[test]
+    fn test_native_game_lost_on_time() {
+        let mut contract = native_joined();
+        assert_eq!(contract.status, GameStatus::Ready);
+        assert!(contract.start().is_none());
+
+        play_turn(&mut contract, "alice.near", 20);
+        let clock = contract.clock().unwrap();
+        assert_eq!(clock.remaining[0].0, 45_000_000_000);
+        assert_eq!(clock.running, Some(2));
+
+        call_from("carol.near", 70);
+        assert!(!contract.is_flagged());
+        call_from("carol.near", 80);
+        contract.claim_timeout();
+        assert_eq!(count_events("flag_fall"), 1);
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.winner.account_id, account("alice.near"));
+        assert_eq!(settlement.winner.payout, U128(1_800));
+        assert_eq!(contract.clock().unwrap().running, None);
+
+        let leaderboard = contract.leaderboard(None, None);
+        assert_eq!(leaderboard.len(), 2);
+        assert_eq!(leaderboard[0].account_id, account("alice.near"));
+        assert_eq!(leaderboard[0].profile.rating, 1_516);
+        assert_eq!(contract.leaderboard(Some(1), Some(5))[0].profile.lost, 1);
+    }
+
+    /// Native game won by bob, scoring his last cube on his own move.
+    fn won_by_bob() -> Contract {
+        let mut contract = native_joined();
+        win_as_bob(&mut contract);
+        contract
+    }
+
+    fn win_as_bob(contract: &mut Contract) {
+        call_from("bob.near", 10);
+        let game = contract.game.as_mut().unwrap();
+        game.active_player = 2;
+        game.phase = GamePhase::End;
+        contract.is_finished = true;
+        let (alice, bob) = (
+            contract.first_player().clone(),
+            contract.second_player().clone(),
+        );
+        contract.check_winner(alice, bob);
+    }
+
+    #[test]
+    #[should_panic(expected = "Game is finished, no moves allowed")]
+    fn test_no_moves_after_game_over() {
+        let mut contract = won_by_bob();
+        assert_eq!(contract.fees().accrued[0].amount, U128(200));
+        call_from("bob.near", 20);
+        contract.make_move(1, 1, 1, 2);
+    }
+
+    #[test]
+    fn test_fee_is_fixed_when_the_game_is_created() {
+        let mut contract = native_joined();
+        call_from("owner.near", 0);
+        contract.set_fee(5_000);
+        assert_eq!(contract.game_fee_bps, Some(1_000));
+        win_as_bob(&mut contract);
+        assert_eq!(contract.fees().accrued[0].amount, U128(200));
+        assert_eq!(contract.settlement(1).unwrap().fee, U128(200));
+    }
+
+    #[test]
+    #[should_panic(expected = "game 1 is already settled")]
+    fn test_game_is_settled_once() {
+        let mut contract = won_by_bob();
+        assert_eq!(count_events("payout"), 1);
+        assert_eq!(contract.profile(account("bob.near")).unwrap().won, 1);
+        contract.pay_out(account("bob.near"), 0, 0);
+    }
+
+    #[test]
+    fn test_leaderboard_follows_ratings() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        let (alice, bob, carol) = (
+            account("alice.near"),
+            account("bob.near"),
+            account("carol.near"),
+        );
+        contract.record_result(&alice, &bob);
+        contract.record_result(&carol, &alice);
+        contract.record_result(&carol, &bob);
+
+        let leaderboard = contract.leaderboard(None, None);
+        let ranked: Vec<_> = leaderboard
+            .iter()
+            .map(|entry| (entry.account_id.as_str(), entry.profile.rating))
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                ("carol.near", 1_531),
+                ("alice.near", 1_499),
+                ("bob.near", 1_470)
+            ]
+        );
+        assert_eq!(contract.leaderboard(Some(2), Some(1))[0].account_id, bob);
+        assert!(contract.leaderboard(Some(3), None).is_empty());
+    }
+
+    #[test]
+    fn test_winner_is_paid_out() {
+        let contract = won_by_bob();
+
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.winner.account_id, account("bob.near"));
+        assert_eq!(settlement.winner.payout, U128(1_800));
+        assert_eq!(settlement.loser.account_id, account("alice.near"));
+        assert_eq!(settlement.loser.payout, U128(0));
+        let payout = get_logs()
+            .into_iter()
+            .find(|log| log.contains("\"event\":\"payout\""))
+            .unwrap();
+        assert!(payout.contains("\"account_id\":\"bob.near\""));
+    }
+
+    #[test]
+    fn test_only_whitelisted_stakes_accepted() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(10), U128(STAKE));
+        contract.add_token(account("usdc.near"), U128(1), U128(5));
+        contract.remove_token(account("usdc.near"));
+        assert_eq!(contract.accepted_tokens().len(), 1);
+        storage_deposited(&mut contract, "alice.near");
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+
+        call_from("usdc.near", 0);
+        let refused = contract.ft_on_transfer(account("alice.near"), U128(5), create.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(5))));
+        call_from("wrap.near", 0);
+        for amount in [9, STAKE + 1] {
+            let refused =
+                contract.ft_on_transfer(account("alice.near"), U128(amount), create.into());
+            assert!(matches!(refused, PromiseOrValue::Value(U128(refused)) if refused == amount));
+        }
+        assert!(contract.first.is_none());
+        let accepted = contract.ft_on_transfer(account("alice.near"), U128(10), create.into());
+        assert!(matches!(accepted, PromiseOrValue::Value(U128(0))));
+    }
+
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn test_only_owner_whitelists_tokens() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        call_from("alice.near", 0);
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+    }
+
+    #[test]
+    fn test_native_stakes_escrowed() {
+        let mut contract = native_joined();
+        assert_eq!(contract.currency(), &Currency::Near);
+        assert!(!contract.streamed);
+        assert_eq!(contract.first_player().deposit(), U128(STAKE));
+        assert_eq!(contract.second_player().deposit(), U128(STAKE));
+        // the stakes stay with the contract, nothing is sent anywhere
+        assert!(get_created_receipts().is_empty());
+
+        call_from("carol.near", 100);
+        contract.claim_timeout();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, account("bob.near"));
+        assert_eq!(
+            receipts[0].actions,
+            vec![VmAction::Transfer { deposit: 1_800 }]
+        );
+
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.set_near_stakes(Some(StakeLimits {
+            min_stake: U128(1),
+            max_stake: U128(STAKE),
+        }));
+        storage_deposited(&mut contract, "alice.near");
+        pay_from("alice.near", 0, STAKE);
+        let params = r#"{"tokens_per_sec": "0"}"#;
+        contract.create_game(near_sdk::serde_json::from_str(params).unwrap());
+        call_from("alice.near", 10);
+        drop(contract.cancel_registration());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, account("alice.near"));
+        assert_eq!(
+            receipts[0].actions,
+            vec![VmAction::Transfer { deposit: STAKE }]
+        );
+    }
+
+    #[test]
+    fn test_expired_registration_is_refunded() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        storage_deposited(&mut contract, "alice.near");
+        storage_deposited(&mut contract, "carol.near");
+        call_from("wrap.near", 0);
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+        contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.into());
+
+        // once expired, the next game replaces alice's registration
+        let expired = REGISTRATION_TIMEOUT / 1_000_000_000;
+        call_from("wrap.near", expired);
+        contract.ft_on_transfer(account("carol.near"), U128(STAKE), create.into());
+        assert_eq!(count_events("registration_cancelled"), 1);
+        assert_eq!(count_events("refund"), 1);
+        assert_eq!(contract.first_player().account(), &account("carol.near"));
+        assert_eq!(contract.game_id, 2);
+        let storage = contract.storage_balance_of(account("alice.near")).unwrap();
+        assert_eq!(storage.available.0, storage_cost(GAME_STORAGE_BYTES));
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be reset until settled")]
+    fn test_no_reset_during_registration() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        storage_deposited(&mut contract, "alice.near");
+        call_from("wrap.near", 0);
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+        contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.into());
+
+        call_from("owner.near", 10);
+        contract.reset(None);
+    }
+
+    #[test]
+    fn test_reset_after_settlement() {
+        let mut contract = native_joined();
+        call_from("carol.near", 100);
+        contract.claim_timeout();
+
+        contract.reset(Some(2));
+        assert!(contract.game.is_none() && contract.first.is_none());
+        assert_eq!(contract.num_cubes, Some(2));
+        assert_eq!(contract.game_id, 1);
+        assert!(contract.settlement(1).is_some());
+    }
+
+    #[test]
+    fn test_reset_keeps_streaming_contract() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.connect_streaming_contract(account("streaming.near"));
+        call_from("carol.near", 0);
+        contract.reset(None);
+        assert_eq!(contract.streaming_id, Some(account("streaming.near")));
+    }
+
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn test_only_owner_connects_streaming_contract() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        call_from("mallory.near", 0);
+        contract.connect_streaming_contract(account("streaming.near"));
+    }
+
+    #[test]
+    fn test_delivered_payout_completes_settlement() {
+        let mut contract = native_joined();
+        call_from("carol.near", 100);
+        contract.claim_timeout();
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.payout_status, PayoutStatus::Pending);
+
+        let winner = settlement.winner.account_id.clone();
+        callback(true);
+        contract.on_payout(
+            winner.clone(),
+            Currency::Near,
+            settlement.winner.payout,
+            Some(1),
+        );
+        let settlement = contract.settlement(1).unwrap();
+        assert_eq!(settlement.payout_status, PayoutStatus::Completed);
+        assert!(contract.claimable(winner).is_empty());
+        assert_eq!(count_events("payout_failed"), 0);
+    }
+
+    #[test]
+    fn test_game_storage_covers_what_a_game_leaves() {
+        // the longest account IDs take the most storage
+        let alice = format!("{}.near", "a".repeat(59));
+        let bob = format!("{}.near", "b".repeat(59));
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.set_near_stakes(Some(StakeLimits {
+            min_stake: U128(1),
+            max_stake: U128(STAKE),
+        }));
+        storage_deposited(&mut contract, &alice);
+        pay_from(&alice, 0, STAKE);
+        let params = r#"{"tokens_per_sec": "1", "time_control": {"seconds": 60}}"#;
+        contract.create_game(near_sdk::serde_json::from_str(params).unwrap());
+        pay_from(&bob, 0, STAKE);
+        contract.join_game(1);
+
+        // the settlement record and the players' new profiles
+        call_from("carol.near", 100);
+        let before = env::storage_usage();
+        contract.claim_timeout();
+        let settled = env::storage_usage() - before;
+        // the winnings, if their payout fails
+        let settlement = contract.settlement(1).unwrap();
+        callback(false);
+        let before = env::storage_usage();
+        contract.on_payout(
+            settlement.winner.account_id,
+            Currency::Near,
+            settlement.winner.payout,
+            Some(1),
+        );
+        let claimable = env::storage_usage() - before;
+        // a token game stores the token ID in both
+        let token_id = 2 * 64;
+        assert!(settled + claimable + token_id <= GAME_STORAGE_BYTES);
+    }
+
+    #[test]
+    fn test_failed_fee_withdrawal_is_restored() {
+        let mut contract = native_joined();
+        call_from("carol.near", 100);
+        contract.claim_timeout();
+        assert_eq!(contract.fees().accrued[0].amount, U128(200));
+
+        call_from("owner.near", 100);
+        contract.withdraw_fees(None, U128(150));
+        assert_eq!(contract.fees().accrued[0].amount, U128(50));
+        callback(false);
+        contract.on_fees_withdrawn(Currency::Near, U128(150));
+        assert_eq!(contract.fees().accrued[0].amount, U128(200));
+
+        call_from("owner.near", 100);
+        contract.withdraw_fees(None, U128(200));
+        callback(true);
+        contract.on_fees_withdrawn(Currency::Near, U128(200));
+        assert!(contract.fees().accrued.is_empty());
+    }
+
+    #[test]
+    fn test_failed_stream_creation_refunds_players() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        let alice_stream = stream_created(&mut contract, &clock, "alice.near");
+
+        contract.on_stream_deposit(Err(PromiseError::Failed), account("bob.near"));
+        assert_eq!(contract.status, GameStatus::RegistrationFailed);
+        assert!(contract.first.is_none() && contract.second.is_none());
+        assert!(contract.game.is_none());
+        // alice is refunded once her stake returns from her stopped stream
+        assert_eq!(clock.stream(&alice_stream).status, StreamStatus::Finished);
+        assert_eq!(count_events("refund"), 1);
+        assert_eq!(count_events("registration_failed"), 1);
+        // the game was never played, its storage is available again
+        let storage = contract.storage_balance_of(account("alice.near")).unwrap();
+        assert_eq!(storage.available.0, storage_cost(GAME_STORAGE_BYTES));
+
+        callback(true);
+        let wrap = Currency::Token(account("wrap.near"));
+        contract.on_registration_stream_stopped(account("alice.near"), wrap, U128(STAKE));
+        assert_eq!(count_events("refund"), 1);
+        assert_eq!(get_created_receipts()[0].receiver_id, account("wrap.near"));
+    }
+
+    #[test]
+    fn test_stream_not_stopped_keeps_refund_claimable() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        stream_created(&mut contract, &clock, "alice.near");
+        contract.on_stream_deposit(Err(PromiseError::Failed), account("bob.near"));
+
+        callback(false);
+        let wrap = Currency::Token(account("wrap.near"));
+        contract.on_registration_stream_stopped(account("alice.near"), wrap, U128(STAKE));
+        assert_eq!(count_events("refund"), 0);
+        assert!(get_created_receipts().is_empty());
+        let claimable = contract.claimable(account("alice.near"));
+        assert_eq!(claimable[0].amount, U128(STAKE));
+    }
+
+    #[test]
+    fn test_partial_deposit_stops_its_stream() {
+        for found in [true, false] {
+            let clock = MockClock::install();
+            let mut contract = joined();
+            call_from("game.near", 0);
+            contract.on_stream_deposit(Ok(U128(STAKE - 1)), account("alice.near"));
+            assert_eq!(contract.status, GameStatus::CreatingStreams);
+
+            let account_json = if found {
+                Ok(clock.account_json())
+            } else {
+                Err(PromiseError::Failed)
+            };
+            contract.on_partial_stream_id(account_json, account("alice.near"), U128(STAKE - 1));
+            assert_eq!(contract.status, GameStatus::RegistrationFailed);
+            assert!(contract.first.is_none() && contract.second.is_none());
+            let refunds: Vec<String> = get_logs()
+                .into_iter()
+                .filter(|log| log.contains("\"event\":\"refund\""))
+                .collect();
+            if found {
+                // alice waits for her stream to be stopped
+                assert_eq!(clock.stream("stream-1").status, StreamStatus::Finished);
+                assert_eq!(refunds.len(), 1);
+            } else {
+                // alice only gets back what roke.to didn't take
+                assert_eq!(refunds.len(), 2);
+                assert!(refunds[0].contains("\"amount\":\"1\""));
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_registration_steps_refund_players() {
+        for step in ["empty deposit", "stream id query"] {
+            let clock = MockClock::install();
+            let mut contract = joined();
+            call_from("game.near", 0);
+            match step {
+                "empty deposit" => {
+                    contract.on_stream_deposit(Ok(U128(0)), account("alice.near"));
+                }
+                _ => {
+                    contract.on_stream_deposit(Ok(U128(STAKE)), account("alice.near"));
+                    contract
+                        .query_stream_id_callback(Err(PromiseError::Failed), account("alice.near"));
+                }
+            }
+            assert_eq!(contract.status, GameStatus::RegistrationFailed, "{}", step);
+            assert!(contract.first.is_none() && contract.second.is_none());
+            assert_eq!(count_events("refund"), 2, "{}", step);
+            // both stakes are sent back by wrap.near
+            let refunds = get_created_receipts()
+                .iter()
+                .filter(|receipt| receipt.receiver_id == account("wrap.near"))
+                .count();
+            assert_eq!(refunds, 2, "{}", step);
+            assert_eq!(clock.last_created_stream(), "stream-1");
+        }
+    }
+
+    #[test]
+    fn test_game_creation_takes_storage() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+
+        call_from("wrap.near", 0);
+        let refused = contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(STAKE))));
+
+        storage_deposited(&mut contract, "alice.near");
+        call_from("wrap.near", 0);
+        let accepted = contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.into());
+        assert!(matches!(accepted, PromiseOrValue::Value(U128(0))));
+        let storage = contract.storage_balance_of(account("alice.near")).unwrap();
+        assert_eq!(storage.available.0, 0);
+
+        pay_from("alice.near", 0, 1);
+        assert!(contract.storage_withdraw(None).available.0 == 0);
+        call_from("alice.near", 0);
+        drop(contract.cancel_registration());
+        let storage = contract.storage_balance_of(account("alice.near")).unwrap();
+        assert_eq!(storage.available.0, storage_cost(GAME_STORAGE_BYTES));
+    }
+
+    #[test]
+    fn test_pause_freezes_clock() {
+        let mut contract = native_joined();
+        play_turn(&mut contract, "alice.near", 20);
+
+        call_from("owner.near", 30);
+        contract.pause();
+        assert!(contract.is_paused());
+        call_from("wrap.near", 30);
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1"}}"#;
+        let refused = contract.ft_on_transfer(account("carol.near"), U128(STAKE), create.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(STAKE))));
+
+        call_from("owner.near", 1_000);
+        contract.unpause();
+        // bob's 10 seconds before the pause are gone, the rest is still there
+        assert_eq!(contract.clock().unwrap().remaining[1].0, 50_000_000_000);
+        assert_eq!(count_events("unpaused"), 1);
+        play_turn(&mut contract, "bob.near", 1_010);
+    }
+
+    #[test]
+    fn test_pause_holds_the_running_stream() {
+        let clock = MockClock::install();
+        let mut contract = joined();
+        stream_created(&mut contract, &clock, "alice.near");
+        let bob_stream = stream_created(&mut contract, &clock, "bob.near");
+        contract.start();
+
+        // bob's stream runs while alice thinks
+        call_from("owner.near", 30);
+        assert!(contract.pause().is_some());
+        assert_eq!(clock.stream(&bob_stream).status, StreamStatus::Paused);
+        callback(true);
+        contract.on_stream_action(account("bob.near"), StreamStatus::Paused);
+        assert!(contract
+            .stream_mismatches()
+            .iter()
+            .all(|mismatch| mismatch.expected == StreamStatus::Paused));
+
+        call_from("owner.near", 1_000);
+        assert!(contract.unpause().is_some());
+        call_from("alice.near", 1_010);
+        let bob = clock.stream(&bob_stream);
+        assert_eq!(bob.status, StreamStatus::Active);
+        // nothing was streamed to bob while the contract was paused
+        assert_eq!(bob.withdrawn, 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "contract is paused")]
+    fn test_no_moves_while_paused() {
+        let mut contract = native_joined();
+        call_from("owner.near", 10);
+        contract.pause();
+        play_turn(&mut contract, "alice.near", 20);
+    }
+
+    #[test]
+    fn test_accept_challenge() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        storage_deposited(&mut contract, "alice.near");
+
+        call_from("wrap.near", 0);
+        let post =
+            r#"{"PostChallenge": {"tokens_per_sec": "1", "min_rating": 1400, "max_rating": 1600}}"#;
+        let posted = contract.ft_on_transfer(account("alice.near"), U128(STAKE), post.into());
+        assert!(matches!(posted, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.challenges(None, None).len(), 1);
+        assert!(contract.first.is_none());
+
+        let accept = r#"{"AcceptChallenge": {"challenge_id": 1}}"#;
+        let refused = contract.ft_on_transfer(account("bob.near"), U128(STAKE - 1), accept.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(999))));
+        let strong = Profile {
+            rating: 1_700,
+            ..Profile::default()
+        };
+        contract.profiles.insert(&account("carol.near"), &strong);
+        let refused = contract.ft_on_transfer(account("carol.near"), U128(STAKE), accept.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(STAKE))));
+
+        let accepted = contract.ft_on_transfer(account("bob.near"), U128(STAKE), accept.into());
+        assert!(matches!(accepted, PromiseOrValue::Value(U128(0))));
+        assert_eq!(count_events("challenge_accepted"), 1);
+        assert_eq!(contract.status, GameStatus::Ready);
+        assert_eq!(contract.first_player().account(), &account("alice.near"));
+        assert_eq!(contract.second_player().account(), &account("bob.near"));
+        assert!(contract.challenges(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_private_game() {
+        call_from("owner.near", 0);
+        let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+        contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+        storage_deposited(&mut contract, "alice.near");
+
+        call_from("wrap.near", 0);
+        let create = r#"{"CreateGame": {"tokens_per_sec": "1", "opponent": "bob.near"}}"#;
+        contract.ft_on_transfer(account("alice.near"), U128(STAKE), create.into());
+        assert_eq!(contract.opponent, Some(account("bob.near")));
+
+        let join = r#"{"JoinGame": {"game_id": 1}}"#;
+        let refused = contract.ft_on_transfer(account("carol.near"), U128(STAKE), join.into());
+        assert!(matches!(refused, PromiseOrValue::Value(U128(STAKE))));
+        assert!(contract.second.is_none());
+        let joined = contract.ft_on_transfer(account("bob.near"), U128(STAKE), join.into());
+        assert!(matches!(joined, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.second_player().account(), &account("bob.near"));
+    }
+
+    /// carol's challenge for native NEAR, accepted by dave while alice and
+    /// bob play.
+    fn challenge_accepted_during_game() -> Contract {
+        let mut contract = native_joined();
+        storage_deposited(&mut contract, "carol.near");
+        pay_from("carol.near", 0, STAKE);
+        let params = r#"{"tokens_per_sec": "1", "max_rating": 1600}"#;
+        contract.post_challenge(near_sdk::serde_json::from_str(params).unwrap());
+        pay_from("dave.near", 0, STAKE);
+        assert!(contract.accept_challenge(1).is_none());
+        assert_eq!(count_events("challenge_accepted"), 1);
+        contract
+    }
+
+    #[test]
+    fn test_accepted_challenge_waits_for_the_game() {
+        let mut contract = challenge_accepted_during_game();
+        assert!(contract.challenges(None, None).is_empty());
+        assert_eq!(
+            contract.accepted_challenges(None, None)[0].opponent,
+            account("dave.near")
+        );
+        assert_eq!(contract.first_player().account(), &account("alice.near"));
+
+        win_as_bob(&mut contract);
+        call_from("carol.near", 20);
+        assert!(contract.reset(None).is_none());
+        assert_eq!(count_events("challenge_started"), 1);
+        assert!(contract.accepted_challenges(None, None).is_empty());
+        assert_eq!(contract.game_id, 2);
+        assert_eq!(contract.status, GameStatus::Ready);
+        assert_eq!(contract.first_player().account(), &account("carol.near"));
+        assert_eq!(contract.second_player().account(), &account("dave.near"));
+        assert!(contract.game.is_some());
+    }
+
+    #[test]
+    fn test_accepted_challenge_cancelled_refunds_both() {
+        let mut contract = challenge_accepted_during_game();
+        call_from("dave.near", 10);
+        contract.cancel_challenge(1);
+        assert!(contract.accepted_challenges(None, None).is_empty());
+        assert_eq!(count_events("refund"), 2);
+        let refunded: Vec<_> = get_created_receipts()
+            .into_iter()
+            .map(|receipt| receipt.receiver_id.to_string())
+            .collect();
+        assert_eq!(refunded, ["carol.near", "dave.near"]);
+    }
+
+    #[test]
+    fn test_creator_chooses_side() {
+        for (side, seed, creator_first) in [
+            ("Second", 0, false),
+            ("Random", 0, true),
+            ("Random", 200, false),
+        ] {
+            call_from("owner.near", 0);
+            let mut contract = Contract::new(Some(1), Some(account("owner.near")));
+            contract.add_token(account("wrap.near"), U128(1), U128(STAKE));
+            storage_deposited(&mut contract, "alice.near");
+
+            call_from("wrap.near", 0);
+            let create = format!(
+                r#"{{"CreateGame": {{"tokens_per_sec": "1", "side": "{}"}}}}"#,
+                side
+            );
+            contract.ft_on_transfer(account("alice.near"), U128(STAKE), create);
+            // a random side isn't drawn before the opponent joins
+            assert!(registered_as(if side == "Second" { 2 } else { 1 }));
+            testing_env!(VMContextBuilder::new()
+                .current_account_id(account("game.near"))
+                .predecessor_account_id(account("wrap.near"))
+                .random_seed([seed; 32])
+                .build());
+            let join = r#"{"JoinGame": {"game_id": 1}}"#;
+            contract.ft_on_transfer(account("bob.near"), U128(STAKE), join.into());
+
+            let first = if creator_first {
+                "alice.near"
+            } else {
+                "bob.near"
+            };
+            assert_eq!(
+                contract.first_player().account(),
+                &account(first),
+                "{}",
+                side
+            );
+            assert!(registered_as(if creator_first { 2 } else { 1 }));
+            assert_eq!(contract.first_player().index(), 1);
+            assert_eq!(contract.creator, Some(account("alice.near")));
+            assert_eq!(count_events("sides_assigned"), 1);
+            assert_eq!(contract.game.as_ref().unwrap().active_player, 1);
+        }
     }
 }
@@ -0,0 +1,227 @@
+//! Layouts the contract state has had. The state itself carries no version
+//! tag, so the version of the stored layout is kept under its own key, and
+//! `migrate` reads the state in that layout after a new code is deployed.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::currency::Currency;
+use crate::game::Game;
+use crate::player::{GamePlayerIndex, Player};
+use crate::registration::{GameStatus, REGISTRATION_TIMEOUT};
+use crate::{Contract, ContractExt};
+
+const STATE_KEY: &[u8] = b"STATE";
+const VERSION_KEY: &[u8] = b"VERSION";
+
+/// Version of the layout `Contract` is stored in. Bump it, and add a variant
+/// to `VersionedState` with the previous layout, whenever a field changes.
+pub const STATE_VERSION: u8 = 1;
+
+/// Player of the version 0 state, before stream statuses were tracked.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PlayerV0 {
+    index: GamePlayerIndex,
+    account: AccountId,
+    deposit: U128,
+    stream: Option<String>,
+}
+
+/// The state deployed before versioning: one game for roke.to streamed
+/// tokens.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV0 {
+    game: Option<Game>,
+    is_finished: bool,
+    first: Option<PlayerV0>,
+    second: Option<PlayerV0>,
+    token_id: Option<AccountId>,
+    deposit: u128,
+    tokens_per_sec: String,
+    streaming_id: Option<AccountId>,
+    num_cubes: Option<usize>,
+}
+
+// read once per upgrade, its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+pub enum VersionedState {
+    V0(ContractV0),
+    V1(Contract),
+}
+
+impl VersionedState {
+    /// Read the stored state in the layout of its version.
+    fn read() -> Self {
+        let version = env::storage_read(VERSION_KEY).map_or(0, |version| version[0]);
+        let state = env::storage_read(STATE_KEY).expect("contract is not initialized");
+        match version {
+            0 => Self::V0(parse(&state, version)),
+            1 => Self::V1(parse(&state, version)),
+            _ => env::panic_str(&format!("unknown state version {}", version)),
+        }
+    }
+
+    fn into_current(self) -> Contract {
+        match self {
+            Self::V0(old) => old.into(),
+            Self::V1(contract) => contract,
+        }
+    }
+}
+
+impl From<PlayerV0> for Player {
+    fn from(old: PlayerV0) -> Self {
+        let mut player = Player::new(old.account, old.deposit, old.index);
+        player.stream = old.stream;
+        player
+    }
+}
+
+impl From<ContractV0> for Contract {
+    /// The game goes on with its streams: their statuses are unknown until
+    /// `sync_streams` queries them. An open registration gets the first game
+    /// id and expires as usual.
+    fn from(old: ContractV0) -> Self {
+        let mut contract = Contract::initial(old.num_cubes, env::current_account_id());
+        contract.status = if old.game.is_some() {
+            GameStatus::Ready
+        } else if old.second.is_some() {
+            GameStatus::CreatingStreams
+        } else {
+            GameStatus::Registration
+        };
+        if old.first.is_some() {
+            contract.game_id = 1;
+        }
+        if old.first.is_some() && old.second.is_none() {
+            contract.registration_deadline = Some(env::block_timestamp() + REGISTRATION_TIMEOUT);
+        }
+        contract.streams_started = old.game.is_some();
+        contract.streamed = true;
+        contract.game = old.game;
+        contract.is_finished = old.is_finished;
+        contract.creator = old.first.as_ref().map(|first| first.account.clone());
+        contract.first = old.first.map(Player::from);
+        contract.second = old.second.map(Player::from);
+        contract.currency = old.token_id.map(Currency::Token);
+        contract.deposit = old.deposit;
+        contract.tokens_per_sec = old.tokens_per_sec;
+        contract.streaming_id = old.streaming_id;
+        contract
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Upgrade the state deployed by a previous version of the contract.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let contract = VersionedState::read().into_current();
+        write_state_version();
+        contract
+    }
+}
+
+fn parse<T: BorshDeserialize>(state: &[u8], version: u8) -> T {
+    T::try_from_slice(state)
+        .unwrap_or_else(|_| env::panic_str(&format!("state is not of version {}", version)))
+}
+
+/// Record that the state is stored in the current layout.
+pub(crate) fn write_state_version() {
+    env::storage_write(VERSION_KEY, &[STATE_VERSION]);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn player_v0(name: &str, index: GamePlayerIndex) -> PlayerV0 {
+        PlayerV0 {
+            index,
+            account: account(name),
+            deposit: U128(1_000),
+            stream: Some(format!("stream-{}", index)),
+        }
+    }
+
+    fn deployed(state: ContractV0) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account("game.near"))
+            .predecessor_account_id(account("game.near"))
+            .build());
+        env::storage_write(STATE_KEY, &state.try_to_vec().unwrap());
+    }
+
+    #[test]
+    fn test_migrate_game_in_progress() {
+        let mut game = Game::game_setup(Some(2));
+        game.active_player = 2;
+        deployed(ContractV0 {
+            game: Some(game.clone()),
+            is_finished: false,
+            first: Some(player_v0("alice.near", 1)),
+            second: Some(player_v0("bob.near", 2)),
+            token_id: Some(account("wrap.near")),
+            deposit: 1_000,
+            tokens_per_sec: "1".to_string(),
+            streaming_id: Some(account("streaming.near")),
+            num_cubes: Some(2),
+        });
+
+        let contract = Contract::migrate();
+        let migrated_game = contract.game.as_ref().unwrap();
+        assert_eq!(migrated_game.active_player, 2);
+        assert_eq!(migrated_game.board.len(), game.board.len());
+        assert_eq!(contract.status, GameStatus::Ready);
+        assert!(contract.streamed && contract.streams_started);
+        assert_eq!(
+            contract.currency,
+            Some(Currency::Token(account("wrap.near")))
+        );
+        assert_eq!(contract.streaming_id, Some(account("streaming.near")));
+        let second = contract.second_player();
+        assert_eq!(second.account(), &account("bob.near"));
+        assert_eq!(second.stream(), Some(&"stream-2".to_string()));
+        assert_eq!(second.stream_status, None);
+        assert_eq!(contract.owner_id, account("game.near"));
+        assert_eq!(env::storage_read(VERSION_KEY), Some(vec![STATE_VERSION]));
+
+        // migrating the current state changes nothing
+        env::state_write(&contract);
+        let migrated = Contract::migrate();
+        assert_eq!(migrated.game.unwrap().active_player, 2);
+        assert_eq!(migrated.game_id, contract.game_id);
+    }
+
+    #[test]
+    fn test_migrate_open_registration() {
+        deployed(ContractV0 {
+            game: None,
+            is_finished: false,
+            first: Some(player_v0("alice.near", 1)),
+            second: None,
+            token_id: Some(account("wrap.near")),
+            deposit: 1_000,
+            tokens_per_sec: "1".to_string(),
+            streaming_id: Some(account("streaming.near")),
+            num_cubes: None,
+        });
+
+        let contract = Contract::migrate();
+        assert_eq!(contract.status, GameStatus::Registration);
+        assert_eq!(contract.game_id, 1);
+        assert!(contract.registration_deadline.is_some());
+        assert!(contract.game.is_none() && contract.second.is_none());
+    }
+}
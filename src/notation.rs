@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::game::GamePhase;
+use crate::position::GameCubePosition;
+
+/// Kind of a move as written in notation: `-` marks a Roll (`c7-c6`),
+/// `x` marks a Hop (`c6xe4`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveKind {
+    Roll,
+    Hop,
+}
+
+impl MoveKind {
+    /// The kind of move expected in a game phase, if any move is allowed.
+    pub fn for_phase(phase: GamePhase) -> Option<MoveKind> {
+        match phase {
+            GamePhase::Roll => Some(MoveKind::Roll),
+            GamePhase::Hop => Some(MoveKind::Hop),
+            GamePhase::End => None,
+        }
+    }
+
+    fn separator(&self) -> char {
+        match self {
+            MoveKind::Roll => '-',
+            MoveKind::Hop => 'x',
+        }
+    }
+}
+
+/// A single cube move in algebraic notation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameMove {
+    pub from: GameCubePosition,
+    pub to: GameCubePosition,
+    pub kind: MoveKind,
+}
+
+impl GameMove {
+    /// Parse `c7-c6` (Roll) or `c6xe4` (Hop).
+    pub fn parse(mv: &str) -> Option<GameMove> {
+        let mv = mv.trim();
+        let (kind, (from, to)) = if let Some(squares) = mv.split_once('-') {
+            (MoveKind::Roll, squares)
+        } else {
+            (MoveKind::Hop, mv.split_once(['x', 'X'])?)
+        };
+        Some(GameMove {
+            from: GameCubePosition::from_square(from)?,
+            to: GameCubePosition::from_square(to)?,
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for GameMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.from, self.kind.separator(), self.to)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_notation() {
+        let d2 = GameCubePosition::from_square("d2").unwrap();
+        assert_eq!(d2, GameCubePosition { x: 4, y: 2 });
+        assert_eq!(d2.to_string(), "d2");
+        assert_eq!(
+            GameCubePosition::from_square("G7"),
+            GameCubePosition::from(7, 7)
+        );
+        assert!(GameCubePosition::from_square("h1").is_none());
+        assert!(GameCubePosition::from_square("a8").is_none());
+        assert!(GameCubePosition::from_square("a0").is_none());
+        assert!(GameCubePosition::from_square("a12").is_none());
+        assert!(GameCubePosition::from_square("").is_none());
+    }
+
+    #[test]
+    fn test_move_notation() {
+        let roll = GameMove::parse("c7-c6").unwrap();
+        assert_eq!(roll.kind, MoveKind::Roll);
+        assert_eq!(roll.from, GameCubePosition { x: 3, y: 7 });
+        assert_eq!(roll.to, GameCubePosition { x: 3, y: 6 });
+        assert_eq!(roll.to_string(), "c7-c6");
+
+        let hop = GameMove::parse(" c6xe4 ").unwrap();
+        assert_eq!(hop.kind, MoveKind::Hop);
+        assert_eq!(hop.to, GameCubePosition { x: 5, y: 4 });
+        assert_eq!(hop.to_string(), "c6xe4");
+
+        assert!(GameMove::parse("c7c6").is_none());
+        assert!(GameMove::parse("c7-").is_none());
+        assert!(GameMove::parse("c7-c6-c5").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+//! Emergency switch: while paused nobody can move, start a game or register,
+//! but refunds, withdrawals and claims still go through.
+
+use near_sdk::{env, near_bindgen, require, Promise};
+
+use crate::clock::StreamStatus;
+use crate::events::Event;
+use crate::game::GamePhase;
+use crate::{Contract, ContractExt};
+
+#[near_bindgen]
+impl Contract {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Halt play and registrations, freezing the contract's clock and pausing
+    /// the stream running in a streamed game. Owner only.
+    pub fn pause(&mut self) -> Option<Promise> {
+        self.assert_owner();
+        require!(!self.paused, "contract is already paused");
+        self.paused = true;
+        if let Some(clock) = self.chess_clock.as_mut() {
+            clock.hold(env::block_timestamp());
+        }
+        Event::Paused {
+            account_id: env::predecessor_account_id(),
+        }
+        .emit();
+        self.running_stream_action(StreamStatus::Paused)
+    }
+
+    /// Resume play and registrations; the active player's clock, or their
+    /// opponent's stream, runs again. Owner only.
+    pub fn unpause(&mut self) -> Option<Promise> {
+        self.assert_owner();
+        require!(self.paused, "contract is not paused");
+        self.paused = false;
+        if let (Some(clock), Some(game)) = (self.chess_clock.as_mut(), self.game.as_ref()) {
+            if game.phase != GamePhase::End {
+                clock.resume(game.active_player, env::block_timestamp());
+            }
+        }
+        Event::Unpaused {
+            account_id: env::predecessor_account_id(),
+        }
+        .emit();
+        self.running_stream_action(StreamStatus::Active)
+    }
+
+    pub(crate) fn assert_not_paused(&self) {
+        require!(!self.paused, "contract is paused");
+    }
+
+    pub(crate) fn check_not_paused(&self) -> Result<(), String> {
+        if self.paused {
+            return Err("contract is paused".to_string());
+        }
+        Ok(())
+    }
+}
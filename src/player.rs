@@ -6,6 +6,7 @@ use near_sdk::{
     AccountId,
 };
 
+use crate::clock::StreamStatus;
 use crate::position::GameCubePosition;
 
 /// The index of a player to whom the cube belongs (should be 1 or 2).
@@ -27,6 +28,8 @@ pub struct Player {
     account: AccountId,
     deposit: U128,
     pub stream: Option<String>,
+    /// Status of the stream, as last confirmed by the streaming contract.
+    pub stream_status: Option<StreamStatus>,
 }
 
 impl Player {
@@ -36,9 +39,14 @@ impl Player {
             account,
             deposit,
             stream: None,
+            stream_status: None,
         }
     }
 
+    pub fn index(&self) -> GamePlayerIndex {
+        self.index
+    }
+
     pub fn account(&self) -> &AccountId {
         &self.account
     }
@@ -50,4 +58,9 @@ impl Player {
     pub fn stream(&self) -> Option<&String> {
         self.stream.as_ref()
     }
+
+    /// Take `amount` the contract can't give back off the deposit.
+    pub fn withhold(&mut self, amount: u128) {
+        self.deposit = U128(self.deposit.0 - amount);
+    }
 }
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     serde::{Deserialize, Serialize},
@@ -16,12 +18,28 @@ pub struct GameCubePosition {
 impl GameCubePosition {
     pub fn from(x: i8, y: i8) -> Option<GameCubePosition> {
         if (1..=7).contains(&x) && (1..=7).contains(&y) {
-            Some(GameCubePosition {
-                x: x as i8,
-                y: y as i8,
-            })
+            Some(GameCubePosition { x, y })
         } else {
             None
         }
     }
+
+    /// Parse a square in algebraic form: column `a..g` (x) and row `1..7` (y),
+    /// e.g. `d2` is `(4, 2)`.
+    pub fn from_square(square: &str) -> Option<GameCubePosition> {
+        let mut chars = square.trim().chars();
+        let col = chars.next()?.to_ascii_lowercase();
+        let row = chars.next()?.to_digit(10)?;
+        if chars.next().is_some() || !('a'..='g').contains(&col) {
+            return None;
+        }
+        GameCubePosition::from(col as i8 - 'a' as i8 + 1, row as i8)
+    }
+}
+
+/// Formats the position as an algebraic square, e.g. `d2`.
+impl fmt::Display for GameCubePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x as u8 - 1) as char, self.y)
+    }
 }
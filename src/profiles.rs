@@ -0,0 +1,165 @@
+//! Players' records and ELO ratings, kept across games.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{near_bindgen, AccountId};
+
+use crate::{Contract, ContractExt};
+
+/// Rating of a player's first game.
+pub const INITIAL_RATING: u32 = 1_500;
+
+/// Most a rating can change in one game.
+const K_FACTOR: f64 = 32.0;
+
+const MAX_LEADERBOARD_LIMIT: u64 = 100;
+
+/// Place of a player in the leaderboard: keys in ascending order go from the
+/// best rating down, ties going to the most wins, then by account.
+pub(crate) type Rank = (u32, u32, AccountId);
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Profile {
+    pub games_played: u32,
+    pub won: u32,
+    pub lost: u32,
+    /// Deblockle games always have a winner, this is never counted yet.
+    pub drawn: u32,
+    pub rating: u32,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            games_played: 0,
+            won: 0,
+            lost: 0,
+            drawn: 0,
+            rating: INITIAL_RATING,
+        }
+    }
+}
+
+impl Profile {
+    fn rank(&self, account_id: &AccountId) -> Rank {
+        (
+            u32::MAX - self.rating,
+            u32::MAX - self.won,
+            account_id.clone(),
+        )
+    }
+
+    /// Count a game against an opponent rated `opponent_rating`, where
+    /// `score` is 1 for a win, 0.5 for a draw and 0 for a loss.
+    fn record(&mut self, score: f64, opponent_rating: u32) {
+        let difference = f64::from(opponent_rating) - f64::from(self.rating);
+        let expected = 1.0 / (1.0 + 10f64.powf(difference / 400.0));
+        let rating = f64::from(self.rating) + K_FACTOR * (score - expected);
+        self.rating = rating.round().max(0.0) as u32;
+        self.games_played += 1;
+        if score == 1.0 {
+            self.won += 1;
+        } else if score == 0.0 {
+            self.lost += 1;
+        } else {
+            self.drawn += 1;
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LeaderboardEntry {
+    pub account_id: AccountId,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Record of `account_id`, `null` until a game of theirs is settled.
+    pub fn profile(&self, account_id: AccountId) -> Option<Profile> {
+        self.profiles.get(&account_id)
+    }
+
+    /// Players by rating, best first: `limit` of them (10 by default, at most
+    /// 100) starting at `from_index`.
+    pub fn leaderboard(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<LeaderboardEntry> {
+        self.ranking
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(10).min(MAX_LEADERBOARD_LIMIT) as usize)
+            .map(|((_, _, account_id), _)| LeaderboardEntry {
+                profile: self
+                    .profiles
+                    .get(&account_id)
+                    .expect("ranked without a profile"),
+                account_id,
+            })
+            .collect()
+    }
+
+    /// Rate both players of a finished game, once it is settled.
+    pub(crate) fn record_result(&mut self, winner_id: &AccountId, loser_id: &AccountId) {
+        let mut winner = self.profiles.get(winner_id).unwrap_or_default();
+        let mut loser = self.profiles.get(loser_id).unwrap_or_default();
+        let (winner_rating, loser_rating) = (winner.rating, loser.rating);
+        winner.record(1.0, loser_rating);
+        loser.record(0.0, winner_rating);
+        self.save_profile(winner_id, &winner);
+        self.save_profile(loser_id, &loser);
+    }
+
+    /// Store the profile of `account_id`, moving them to their new rank.
+    fn save_profile(&mut self, account_id: &AccountId, profile: &Profile) {
+        if let Some(previous) = self.profiles.insert(account_id, profile) {
+            self.ranking.remove(&previous.rank(account_id));
+        }
+        self.ranking.insert(&profile.rank(account_id), &());
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rated(rating: u32) -> Profile {
+        Profile {
+            rating,
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn test_elo_ratings() {
+        let (mut winner, mut loser) = (Profile::default(), Profile::default());
+        winner.record(1.0, loser.rating);
+        loser.record(0.0, INITIAL_RATING);
+        assert_eq!(
+            (winner.rating, winner.won, winner.games_played),
+            (1_516, 1, 1)
+        );
+        assert_eq!(
+            (loser.rating, loser.lost, loser.games_played),
+            (1_484, 1, 1)
+        );
+
+        // beating a much weaker player hardly counts, losing to them does
+        let mut strong = rated(2_000);
+        strong.record(1.0, 1_200);
+        assert_eq!(strong.rating, 2_000);
+        let mut strong = rated(2_000);
+        strong.record(0.0, 1_200);
+        assert_eq!(strong.rating, 1_968);
+
+        let mut weak = rated(10);
+        weak.record(0.0, 10);
+        assert_eq!(weak.rating, 0);
+    }
+}
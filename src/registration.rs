@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::Value;
+use near_sdk::{
+    env, is_promise_success, log, near_bindgen, AccountId, Promise, PromiseError, PromiseOrValue,
+};
+
+use crate::chess_clock::ChessClock;
+use crate::clock::{ClockBackend, NewStream, StreamStatus};
+use crate::currency::Currency;
+use crate::events::Event;
+use crate::game::{Game, GameId};
+use crate::game_options::Side;
+use crate::interface::GameParams;
+use crate::player::Player;
+use crate::{Contract, ContractExt};
+
+/// Time after which a first player's registration without an opponent expires:
+/// anyone may cancel it then, and the next deposit replaces it.
+pub const REGISTRATION_TIMEOUT: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Stage of setting up a game between the registered players.
+#[derive(
+    Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum GameStatus {
+    /// Waiting for players' deposits.
+    Registration,
+    /// Both players are in, their roke.to streams are being created.
+    CreatingStreams,
+    /// Streams are created and the game is set up on the board.
+    Ready,
+    /// Creating the streams failed, both deposits were refunded.
+    RegistrationFailed,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Deposit player's stake into a new stream paying out to the player.
+    pub(crate) fn create_stream(&self, player: &Player) -> Promise {
+        let token_id = self
+            .currency()
+            .token_id()
+            .expect("native NEAR stakes are not streamed")
+            .clone();
+        log!("create stream for player {}", player.index());
+        let tokens_per_sec = self
+            .tokens_per_sec
+            .parse()
+            .expect("streaming rate is not a number");
+        self.clock_backend()
+            .create_stream(NewStream {
+                token_id,
+                receiver_id: player.account().clone(),
+                balance: U128(self.deposit),
+                tokens_per_sec: U128(tokens_per_sec),
+                description: format!("{{\"player\": {}}}", player.index()),
+            })
+            .then(Self::ext(env::current_account_id()).on_stream_deposit(player.account().clone()))
+    }
+
+    /// Checks the stake was fully taken by the streaming contract, then asks
+    /// for the new stream ID.
+    #[private]
+    pub fn on_stream_deposit(
+        &mut self,
+        #[callback_result] used: Result<U128, PromiseError>,
+        player_id: AccountId,
+    ) -> PromiseOrValue<U128> {
+        match used {
+            Ok(used) if used.0 == self.deposit => {
+                let current_account = env::current_account_id();
+                let promise = self
+                    .clock_backend()
+                    .get_account(current_account.clone())
+                    .then(Self::ext(current_account).query_stream_id_callback(player_id));
+                PromiseOrValue::Promise(promise)
+            }
+            Ok(used) if used.0 == 0 => {
+                self.fail_registration(format!("roke.to used no tokens for {}'s stream", player_id))
+            }
+            // the stream holds part of the stake, find it to stop it
+            Ok(used) => {
+                let current_account = env::current_account_id();
+                let promise = self
+                    .clock_backend()
+                    .get_account(current_account.clone())
+                    .then(Self::ext(current_account).on_partial_stream_id(player_id, used));
+                PromiseOrValue::Promise(promise)
+            }
+            Err(_) => self.fail_registration(format!("deposit to {}'s stream failed", player_id)),
+        }
+    }
+
+    #[private]
+    pub fn query_stream_id_callback(
+        &mut self,
+        #[callback_result] call_result: Result<HashMap<String, Value>, PromiseError>,
+        player_id: AccountId,
+    ) -> PromiseOrValue<U128> {
+        let stream_id = match last_created_stream(call_result) {
+            Some(id) => id,
+            None => {
+                return self.fail_registration(format!("couldn't query {}'s stream id", player_id))
+            }
+        };
+        log!("[{}] stream id: {}", player_id, stream_id);
+
+        let first = self.first.as_mut().unwrap();
+        if first.account() == &player_id {
+            first.stream = Some(stream_id);
+            first.stream_status = Some(StreamStatus::Initialized);
+            return PromiseOrValue::Promise(self.create_stream(self.second_player()));
+        }
+        let second = self.second.as_mut().unwrap();
+        assert!(second.account() == &player_id, "unknown player ID");
+        second.stream = Some(stream_id);
+        second.stream_status = Some(StreamStatus::Initialized);
+        self.set_up_game();
+        PromiseOrValue::Value(U128(0))
+    }
+
+    /// Records the stream roke.to created with only `used` tokens of
+    /// `player_id`'s stake, so that it is stopped with the registration.
+    /// If it can't be found, the `used` tokens aren't refunded.
+    #[private]
+    pub fn on_partial_stream_id(
+        &mut self,
+        #[callback_result] call_result: Result<HashMap<String, Value>, PromiseError>,
+        player_id: AccountId,
+        used: U128,
+    ) -> PromiseOrValue<U128> {
+        let player = [self.first.as_mut(), self.second.as_mut()]
+            .into_iter()
+            .flatten()
+            .find(|player| player.account() == &player_id)
+            .expect("unknown player ID");
+        match last_created_stream(call_result) {
+            Some(stream_id) => {
+                log!("[{}] stream id: {}", player_id, stream_id);
+                player.stream = Some(stream_id);
+                player.stream_status = Some(StreamStatus::Initialized);
+            }
+            None => {
+                log!(
+                    "couldn't find {}'s stream, {} tokens stay in it",
+                    player_id,
+                    used.0
+                );
+                player.withhold(used.0);
+            }
+        }
+        self.fail_registration(format!(
+            "roke.to used {} of {} tokens for {}'s stream",
+            used.0, self.deposit, player_id
+        ))
+    }
+
+    /// Refunds `amount` to `account_id` once their stream is stopped and the
+    /// stake is back, or keeps it claimable if the stream couldn't be stopped.
+    #[private]
+    pub fn on_registration_stream_stopped(
+        &mut self,
+        account_id: AccountId,
+        currency: Currency,
+        amount: U128,
+    ) -> Option<Promise> {
+        if is_promise_success() {
+            return Some(refund(account_id, &currency, amount));
+        }
+        log!(
+            "stopping {}'s stream failed, {} {} can be claimed with claim_winnings",
+            account_id,
+            amount.0,
+            currency
+        );
+        self.credit_claimable(&account_id, currency, amount.0);
+        None
+    }
+
+    /// Set up the game once the second player is in: right away if the
+    /// stakes are kept by the contract, after creating the players' streams
+    /// otherwise.
+    pub(crate) fn start_registered_game(&mut self) -> PromiseOrValue<U128> {
+        if !self.streamed {
+            self.set_up_game();
+            return PromiseOrValue::Value(U128(0));
+        }
+        self.status = GameStatus::CreatingStreams;
+        PromiseOrValue::Promise(self.create_stream(self.first_player()))
+    }
+
+    /// Put the cubes on the board once both players are in. Without streams
+    /// the first player's clock starts right away.
+    pub(crate) fn set_up_game(&mut self) {
+        let game = Game::game_setup(self.num_cubes);
+        if !self.streamed {
+            let mut clock = ChessClock::new(&self.time_control);
+            clock.start(game.active_player, env::block_timestamp());
+            self.chess_clock = Some(clock);
+        }
+        Event::GameStarted {
+            first: self.first_player().account().clone(),
+            second: self.second_player().account().clone(),
+            num_cubes: game.board.len() / 2,
+        }
+        .emit();
+        self.game = Some(game);
+        self.status = GameStatus::Ready;
+    }
+
+    /// Register the creator of a new game staking `amount` of `currency`.
+    pub(crate) fn open_game(
+        &mut self,
+        sender_id: AccountId,
+        currency: Currency,
+        amount: U128,
+        params: GameParams,
+    ) -> Result<(), String> {
+        self.check_not_paused()?;
+        if self.game.is_some() || self.first.is_some() {
+            return Err(format!(
+                "game {} is already open, join it or wait until it ends",
+                self.game_id
+            ));
+        }
+        if !self.accepted.is_empty() {
+            return Err("accepted challenges are played first, call reset".to_string());
+        }
+        self.check_stake(&currency, amount.0)?;
+        // without a streaming contract the time is kept by the contract
+        let streamed = currency.is_streamed() && self.streaming_id.is_some();
+        params.validate(amount.0, streamed)?;
+        if params.opponent.as_ref() == Some(&sender_id) {
+            return Err("can't invite yourself".to_string());
+        }
+        self.use_game_storage(&sender_id)?;
+        self.streamed = streamed;
+        self.register_first_player(sender_id, currency, amount, params);
+        Ok(())
+    }
+
+    /// Register the opponent in the open game `game_id`, matching its stake.
+    pub(crate) fn join_open_game(
+        &mut self,
+        sender_id: AccountId,
+        currency: Currency,
+        amount: U128,
+        game_id: GameId,
+    ) -> Result<(), String> {
+        self.check_not_paused()?;
+        if self.first.is_none() || game_id != self.game_id {
+            return Err(format!("game {} is not open for registration", game_id));
+        }
+        if let Some(opponent) = &self.opponent {
+            if opponent != &sender_id {
+                return Err(format!("game {} is private to {}", game_id, opponent));
+            }
+        }
+        if self.second.is_some() {
+            return Err("all players are in, registration closed".to_string());
+        }
+        if self.currency() != &currency {
+            return Err(format!(
+                "wrong currency, game is played for {}",
+                self.currency()
+            ));
+        }
+        if self.first_player().deposit() != amount {
+            return Err(format!(
+                "deposit should be: {:?}",
+                self.first_player().deposit()
+            ));
+        }
+
+        self.second = Some(Player::new(sender_id.clone(), amount, 2));
+        self.assign_sides();
+        let player = if self.first_player().account() == &sender_id {
+            1
+        } else {
+            2
+        };
+        Event::PlayerRegistered {
+            account_id: sender_id,
+            player,
+            token_id: currency.token_id().cloned(),
+            amount,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Seat the players on the sides the creator chose: the creator was
+    /// registered first, so they swap if the creator moves second. A random
+    /// side is drawn here, with both stakes in, so that the creator can't
+    /// cancel and open the game again until they draw the side they want.
+    fn assign_sides(&mut self) {
+        let creator_first = match self.side {
+            Side::First => true,
+            Side::Second => false,
+            Side::Random => env::random_seed()[0] < 128,
+        };
+        if !creator_first {
+            let creator = self.first.take().expect("first player is not registered");
+            let opponent = self.second.take().expect("second player is not registered");
+            self.first = Some(Player::new(
+                opponent.account().clone(),
+                opponent.deposit(),
+                1,
+            ));
+            self.second = Some(Player::new(creator.account().clone(), creator.deposit(), 2));
+        }
+        Event::SidesAssigned {
+            first: self.first_player().account().clone(),
+            second: self.second_player().account().clone(),
+            side: self.side,
+        }
+        .emit();
+    }
+
+    /// Refund the first player's registration if it has expired, so that
+    /// a new game can be opened.
+    pub(crate) fn cancel_expired_registration(&mut self) {
+        if self.registration_expired() {
+            log!("first player's registration expired, refunding");
+            drop(self.clear_registration());
+        }
+    }
+
+    /// Withdraw the first player's deposit while nobody has joined yet.
+    /// Anyone may cancel a registration that has expired.
+    pub fn cancel_registration(&mut self) -> Promise {
+        assert!(
+            self.second.is_none() && self.status == GameStatus::Registration,
+            "opponent has already joined, registration can't be cancelled"
+        );
+        let first = self.first.as_ref().expect("first player is not registered");
+        assert!(
+            first.account() == &env::predecessor_account_id() || self.registration_expired(),
+            "only the first player can cancel the registration before it expires"
+        );
+        self.clear_registration()
+    }
+
+    pub(crate) fn registration_expired(&self) -> bool {
+        self.second.is_none()
+            && self
+                .registration_deadline
+                .is_some_and(|deadline| env::block_timestamp() >= deadline)
+    }
+
+    /// Remove the first player and refund the deposit.
+    pub(crate) fn clear_registration(&mut self) -> Promise {
+        let first = self.first.take().expect("first player is not registered");
+        let currency = self
+            .currency
+            .take()
+            .expect("somehow currency is NOT set yet");
+        Event::RegistrationCancelled {
+            account_id: first.account().clone(),
+        }
+        .emit();
+        self.release_game_storage(first.account());
+        self.opponent = None;
+        self.creator = None;
+        self.game_fee_bps = None;
+        self.deposit = 0;
+        self.tokens_per_sec = String::new();
+        self.registration_deadline = None;
+        refund(first.account().clone(), &currency, first.deposit())
+    }
+
+    /// Roll back the registration: refund both players' deposits, stopping
+    /// the streams already created, and clear the players.
+    fn fail_registration(&mut self, reason: String) -> PromiseOrValue<U128> {
+        log!("registration failed: {}", reason);
+        let currency = self
+            .currency
+            .take()
+            .expect("somehow currency is NOT set yet");
+        let clock = self.clock_backend();
+        if let Some(creator) = self.creator.take() {
+            self.release_game_storage(&creator);
+        }
+
+        for player in [self.first.take(), self.second.take()]
+            .into_iter()
+            .flatten()
+        {
+            let account_id = player.account().clone();
+            // the stake of a created stream returns to the contract when stopped
+            let refund = match player.stream() {
+                Some(stream_id) => clock.stop_stream(stream_id.clone()).then(
+                    Self::ext(env::current_account_id()).on_registration_stream_stopped(
+                        account_id,
+                        currency.clone(),
+                        player.deposit(),
+                    ),
+                ),
+                None => refund(account_id, &currency, player.deposit()),
+            };
+            // detached promises are scheduled when dropped
+            drop(refund);
+        }
+
+        Event::RegistrationFailed { reason }.emit();
+        self.game = None;
+        self.opponent = None;
+        self.deposit = 0;
+        self.tokens_per_sec = String::new();
+        self.registration_deadline = None;
+        self.game_fee_bps = None;
+        self.status = GameStatus::RegistrationFailed;
+        // refunds are explicit, nothing to return through ft_resolve_transfer
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+fn refund(account_id: AccountId, currency: &Currency, amount: U128) -> Promise {
+    Event::Refund {
+        account_id: account_id.clone(),
+        token_id: currency.token_id().cloned(),
+        amount,
+    }
+    .emit();
+    currency.transfer(account_id, amount)
+}
+
+/// ID of the stream roke.to created last for the contract, if it told.
+fn last_created_stream(
+    call_result: Result<HashMap<String, Value>, PromiseError>,
+) -> Option<String> {
+    call_result.ok().and_then(|res| {
+        res.get("last_created_stream")
+            .and_then(|id| id.as_str())
+            .map(str::to_string)
+    })
+}
@@ -0,0 +1,171 @@
+//! What each side receives when a game ends.
+//!
+//! While a player thinks, the opponent's roke.to stream pays the opponent
+//! back from their own stake. When the game is over both streams are stopped
+//! and roke.to returns what is left in them to the contract: the winner takes
+//! these remainders of both stakes, less the house fee.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+use crate::currency::Currency;
+use crate::game::GameId;
+use crate::player::Player;
+
+const BPS: u128 = 10_000;
+
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlayerSettlement {
+    pub account_id: AccountId,
+    pub deposit: U128,
+    /// Part of the stake the player's stream paid back during the game.
+    pub streamed: U128,
+    /// Paid out by the contract after the game.
+    pub payout: U128,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PayoutStatus {
+    /// The payout is on its way to the winner.
+    Pending,
+    /// The winner received the payout, or there was nothing to pay.
+    Completed,
+    /// The payout failed, the winner can claim it with `claim_winnings`.
+    Claimable,
+}
+
+/// Settlement of a finished game.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Settlement {
+    pub game_id: GameId,
+    pub currency: Currency,
+    pub winner: PlayerSettlement,
+    pub loser: PlayerSettlement,
+    /// House fee kept in the treasury.
+    pub fee: U128,
+    pub payout_status: PayoutStatus,
+}
+
+impl Settlement {
+    /// Split the stakes left after `winner_streamed` and `loser_streamed`
+    /// tokens were streamed back to the players. The fee is rounded down,
+    /// so that the payouts and the fee add up to the remainders exactly.
+    pub fn compute(
+        game_id: GameId,
+        currency: Currency,
+        fee_bps: u16,
+        (winner, winner_streamed): (&Player, u128),
+        (loser, loser_streamed): (&Player, u128),
+    ) -> Self {
+        // roke.to can't stream more than the stake, don't trust it blindly
+        let winner_streamed = winner_streamed.min(winner.deposit().0);
+        let loser_streamed = loser_streamed.min(loser.deposit().0);
+        let pot = (winner.deposit().0 - winner_streamed) + (loser.deposit().0 - loser_streamed);
+        let fee = mul_bps(pot, fee_bps);
+
+        Self {
+            game_id,
+            currency,
+            winner: PlayerSettlement {
+                account_id: winner.account().clone(),
+                deposit: winner.deposit(),
+                streamed: U128(winner_streamed),
+                payout: U128(pot - fee),
+            },
+            loser: PlayerSettlement {
+                account_id: loser.account().clone(),
+                deposit: loser.deposit(),
+                streamed: U128(loser_streamed),
+                payout: U128(0),
+            },
+            fee: U128(fee),
+            payout_status: if pot > fee {
+                PayoutStatus::Pending
+            } else {
+                PayoutStatus::Completed
+            },
+        }
+    }
+}
+
+/// `amount * bps / 10000`, rounded down, without overflowing.
+fn mul_bps(amount: u128, bps: u16) -> u128 {
+    let bps = u128::from(bps).min(BPS);
+    amount / BPS * bps + amount % BPS * bps / BPS
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, deposit: u128, index: i8) -> Player {
+        Player::new(name.parse().unwrap(), U128(deposit), index)
+    }
+
+    fn settle(fee_bps: u16, winner_streamed: u128, loser_streamed: u128) -> Settlement {
+        let alice = player("alice.near", 1_000, 1);
+        let bob = player("bob.near", 1_000, 2);
+        Settlement::compute(
+            1,
+            Currency::Near,
+            fee_bps,
+            (&alice, winner_streamed),
+            (&bob, loser_streamed),
+        )
+    }
+
+    fn total(settlement: &Settlement) -> u128 {
+        let (winner, loser) = (&settlement.winner, &settlement.loser);
+        winner.streamed.0 + winner.payout.0 + loser.streamed.0 + loser.payout.0 + settlement.fee.0
+    }
+
+    #[test]
+    fn test_settlement_splits_both_remainders() {
+        let settlement = settle(1_000, 300, 100);
+        // 700 + 900 left in the streams, 10% of it is the fee
+        assert_eq!(settlement.fee, U128(160));
+        assert_eq!(settlement.winner.payout, U128(1_440));
+        assert_eq!(settlement.loser.payout, U128(0));
+        assert_eq!(total(&settlement), 2_000);
+    }
+
+    #[test]
+    fn test_settlement_rounding() {
+        // 1999 * 0.0333 = 66.5667
+        let settlement = settle(333, 1, 0);
+        assert_eq!(settlement.fee, U128(66));
+        assert_eq!(settlement.winner.payout, U128(1_933));
+        assert_eq!(total(&settlement), 2_000);
+
+        assert_eq!(mul_bps(u128::MAX, 10_000), u128::MAX);
+        assert_eq!(mul_bps(u128::MAX, 5_000), u128::MAX / 2);
+        assert_eq!(mul_bps(9_999, 1), 0);
+    }
+
+    #[test]
+    fn test_settlement_edge_cases() {
+        let settlement = settle(0, 0, 0);
+        assert_eq!(settlement.fee, U128(0));
+        assert_eq!(settlement.winner.payout, U128(2_000));
+
+        let settlement = settle(10_000, 0, 0);
+        assert_eq!(settlement.fee, U128(2_000));
+        assert_eq!(settlement.winner.payout, U128(0));
+        assert_eq!(settlement.payout_status, PayoutStatus::Completed);
+
+        // all streamed out, or more than the stake reported
+        let settlement = settle(1_000, 1_000, 5_000);
+        assert_eq!(settlement.loser.streamed, U128(1_000));
+        assert_eq!(settlement.winner.payout, U128(0));
+        assert_eq!(settlement.fee, U128(0));
+        assert_eq!(total(&settlement), 2_000);
+    }
+}
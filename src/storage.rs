@@ -0,0 +1,178 @@
+//! NEP-145 storage management: players pay for the state their games leave
+//! in the contract. Opening a game takes the storage of its settlement record,
+//! the players' profiles and ranks, and any winnings left to claim from the
+//! creator's storage balance.
+
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, env, log, near_bindgen, require, AccountId, Balance, Promise};
+
+use crate::{Contract, ContractExt};
+
+/// Storage taken by an account's entry in the storage balances.
+pub const ACCOUNT_STORAGE_BYTES: u64 = 200;
+
+/// Storage left by a game: its settlement record, the players' profiles and
+/// ranks, and winnings whose payout failed. Measured at 1_227 bytes for
+/// players with 64 character account IDs, 1_355 with a token ID as long,
+/// rounded up.
+pub const GAME_STORAGE_BYTES: u64 = 1_400;
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct StorageAccount {
+    /// Everything deposited, the registration minimum included.
+    pub total: Balance,
+    /// Taken by the games the account opened.
+    pub used: Balance,
+}
+
+impl StorageAccount {
+    fn available(&self) -> Balance {
+        self.total - self.used - storage_cost(ACCOUNT_STORAGE_BYTES)
+    }
+
+    fn balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.total),
+            available: U128(self.available()),
+        }
+    }
+}
+
+pub fn storage_cost(bytes: u64) -> Balance {
+    Balance::from(bytes) * env::storage_byte_cost()
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    /// Register `account_id`, the caller by default, or top up its storage
+    /// balance. With `registration_only` anything above the minimum is refunded.
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let min_balance = storage_cost(ACCOUNT_STORAGE_BYTES);
+
+        let mut account = self.storage.get(&account_id).unwrap_or_default();
+        let registered = account.total > 0;
+        let kept = match (registered, registration_only.unwrap_or(false)) {
+            (true, true) => 0,
+            (true, false) => amount,
+            (false, registration_only) => {
+                require!(
+                    amount >= min_balance,
+                    format!("attach at least {} yoctoNEAR to register", min_balance)
+                );
+                if registration_only {
+                    min_balance
+                } else {
+                    amount
+                }
+            }
+        };
+        if amount > kept {
+            Promise::new(env::predecessor_account_id()).transfer(amount - kept);
+        }
+        account.total += kept;
+        self.storage.insert(&account_id, &account);
+        account.balance()
+    }
+
+    /// Withdraw `amount` of the available storage balance, all of it by default.
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = self
+            .storage
+            .get(&account_id)
+            .unwrap_or_else(|| env::panic_str(&format!("{} is not registered", account_id)));
+        let available = account.available();
+        let amount = amount.map_or(available, |amount| amount.0);
+        require!(
+            amount <= available,
+            format!("only {} yoctoNEAR available", available)
+        );
+        if amount > 0 {
+            account.total -= amount;
+            self.storage.insert(&account_id, &account);
+            Promise::new(account_id).transfer(amount);
+        }
+        account.balance()
+    }
+
+    /// Unregister the caller and refund the storage balance. Accounts which
+    /// opened games can't unregister: their records stay in the contract.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let account = match self.storage.get(&account_id) {
+            Some(account) => account,
+            None => return false,
+        };
+        require!(
+            account.used == 0,
+            "account's games are stored, it can't be unregistered"
+        );
+        if force.unwrap_or(false) {
+            log!("nothing to force, {} has no games stored", account_id);
+        }
+        self.storage.remove(&account_id);
+        Promise::new(account_id).transfer(account.total);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(storage_cost(ACCOUNT_STORAGE_BYTES)),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage
+            .get(&account_id)
+            .map(|account| account.balance())
+    }
+}
+
+impl Contract {
+    /// Take the storage of a new game from `account_id`'s storage balance.
+    pub(crate) fn use_game_storage(&mut self, account_id: &AccountId) -> Result<(), String> {
+        let cost = storage_cost(GAME_STORAGE_BYTES);
+        let mut account = self.storage.get(account_id).ok_or_else(|| {
+            format!(
+                "{} has no storage balance, call storage_deposit",
+                account_id
+            )
+        })?;
+        if account.available() < cost {
+            return Err(format!(
+                "a game takes {} yoctoNEAR of storage, only {} available",
+                cost,
+                account.available()
+            ));
+        }
+        account.used += cost;
+        self.storage.insert(account_id, &account);
+        Ok(())
+    }
+
+    /// Give back the storage of a game that was never played.
+    pub(crate) fn release_game_storage(&mut self, account_id: &AccountId) {
+        if let Some(mut account) = self.storage.get(account_id) {
+            account.used = account
+                .used
+                .saturating_sub(storage_cost(GAME_STORAGE_BYTES));
+            self.storage.insert(account_id, &account);
+        }
+    }
+}
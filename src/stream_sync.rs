@@ -0,0 +1,223 @@
+//! Keeping the players' streams in line with the game: every stream action
+//! is confirmed by a callback, and `sync_streams` repairs the streams whose
+//! status differs from what the game expects.
+
+use std::collections::HashMap;
+
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json::Value;
+use near_sdk::{
+    env, is_promise_success, log, near_bindgen, require, AccountId, Promise, PromiseError,
+};
+
+use crate::clock::{ClockBackend, StreamStatus};
+use crate::events::Event;
+use crate::game::GamePhase;
+use crate::player::Player;
+use crate::{Contract, ContractExt};
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StreamMismatch {
+    pub account_id: AccountId,
+    pub stream_id: String,
+    /// Status the game expects.
+    pub expected: StreamStatus,
+    /// Status last confirmed by the streaming contract, `null` if unknown.
+    pub confirmed: Option<StreamStatus>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Players' streams whose confirmed status differs from the expected one.
+    pub fn stream_mismatches(&self) -> Vec<StreamMismatch> {
+        self.players()
+            .filter_map(|player| {
+                let expected = self.expected_stream_status(player)?;
+                if player.stream_status == Some(expected) {
+                    return None;
+                }
+                Some(StreamMismatch {
+                    account_id: player.account().clone(),
+                    stream_id: player.stream()?.clone(),
+                    expected,
+                    confirmed: player.stream_status,
+                })
+            })
+            .collect()
+    }
+
+    /// Query both players' streams, then start, pause or stop the ones not in
+    /// the status the game expects. Anyone can call it.
+    pub fn sync_streams(&mut self) -> Promise {
+        require!(
+            self.streamed && self.second.is_some(),
+            "game has no streams to sync"
+        );
+        let first = self.first_player().stream().expect("no stream").clone();
+        let second = self.second_player().stream().expect("no stream").clone();
+        let clock = self.clock_backend();
+        clock
+            .get_stream(first)
+            .and(clock.get_stream(second))
+            .then(Self::ext(env::current_account_id()).on_streams_queried())
+    }
+
+    /// Records the streams' statuses and repairs the mismatches.
+    #[private]
+    pub fn on_streams_queried(
+        &mut self,
+        #[callback_result] first: Result<HashMap<String, Value>, PromiseError>,
+        #[callback_result] second: Result<HashMap<String, Value>, PromiseError>,
+    ) -> Option<Promise> {
+        let statuses = [first, second].map(|stream| {
+            stream
+                .ok()
+                .and_then(|stream| stream.get("status").and_then(StreamStatus::from_json))
+        });
+        for (player, status) in [self.first.as_mut(), self.second.as_mut()]
+            .into_iter()
+            .flatten()
+            .zip(statuses)
+        {
+            if status.is_some() {
+                player.stream_status = status;
+            } else {
+                log!("couldn't query the stream of {}", player.account());
+            }
+        }
+
+        let mut repair: Option<Promise> = None;
+        for mismatch in self.stream_mismatches() {
+            let player = self.player(&mismatch.account_id).clone();
+            let actions = match (mismatch.confirmed, mismatch.expected) {
+                (None, _) => continue,
+                (Some(StreamStatus::Initialized), StreamStatus::Paused) => {
+                    // roke.to pauses only streams that have been started
+                    vec![StreamStatus::Active, StreamStatus::Paused]
+                }
+                (Some(StreamStatus::Finished), _) | (_, StreamStatus::Initialized) => {
+                    log!(
+                        "stream {} is {:?}, can't make it {:?}",
+                        mismatch.stream_id,
+                        mismatch.confirmed,
+                        mismatch.expected
+                    );
+                    continue;
+                }
+                (_, expected) => vec![expected],
+            };
+            for status in actions {
+                repair = Some(self.stream_action(repair, &player, status));
+            }
+        }
+        repair
+    }
+
+    /// Records the status of the stream of `account_id` if the action succeeded.
+    #[private]
+    pub fn on_stream_action(&mut self, account_id: AccountId, status: StreamStatus) {
+        if !is_promise_success() {
+            log!(
+                "making the stream of {} {:?} failed, call sync_streams",
+                account_id,
+                status
+            );
+            return;
+        }
+        if let Some(player) = [self.first.as_mut(), self.second.as_mut()]
+            .into_iter()
+            .flatten()
+            .find(|player| player.account() == &account_id)
+        {
+            player.stream_status = Some(status);
+        }
+    }
+
+    /// Make `player`'s stream `status` after `after`, confirming it in
+    /// `on_stream_action`.
+    pub(crate) fn stream_action(
+        &self,
+        after: Option<Promise>,
+        player: &Player,
+        status: StreamStatus,
+    ) -> Promise {
+        let clock = self.clock_backend();
+        let account_id = player.account().clone();
+        let stream_id = player.stream().expect("player has no stream").clone();
+        let action = match status {
+            StreamStatus::Active => {
+                Event::StreamStarted {
+                    account_id: account_id.clone(),
+                    stream_id: stream_id.clone(),
+                }
+                .emit();
+                clock.start_stream(stream_id)
+            }
+            StreamStatus::Paused => {
+                Event::StreamPaused {
+                    account_id: account_id.clone(),
+                    stream_id: stream_id.clone(),
+                }
+                .emit();
+                clock.pause_stream(stream_id)
+            }
+            StreamStatus::Finished => {
+                Event::StreamStopped {
+                    account_id: account_id.clone(),
+                    stream_id: stream_id.clone(),
+                }
+                .emit();
+                clock.stop_stream(stream_id)
+            }
+            StreamStatus::Initialized => unreachable!("streams can't be initialized again"),
+        };
+        let action = match after {
+            Some(after) => after.then(action),
+            None => action,
+        };
+        action.then(Self::ext(env::current_account_id()).on_stream_action(account_id, status))
+    }
+
+    /// Pause the stream running in the game, or start it again, if the game
+    /// is streamed and not over.
+    pub(crate) fn running_stream_action(&self, status: StreamStatus) -> Option<Promise> {
+        let game = self.game.as_ref().filter(|_| self.streams_started)?;
+        if game.phase == GamePhase::End {
+            return None;
+        }
+        let player = self
+            .players()
+            .find(|player| player.index() != game.active_player)?;
+        Some(self.stream_action(None, player, status))
+    }
+
+    /// Status of `player`'s stream the game expects: the stream runs while
+    /// the opponent thinks, and neither does while the contract is paused.
+    fn expected_stream_status(&self, player: &Player) -> Option<StreamStatus> {
+        player.stream()?;
+        let game = match &self.game {
+            Some(game) if self.streams_started => game,
+            _ => return Some(StreamStatus::Initialized),
+        };
+        Some(if game.phase == GamePhase::End {
+            StreamStatus::Finished
+        } else if self.paused || game.active_player == player.index() {
+            StreamStatus::Paused
+        } else {
+            StreamStatus::Active
+        })
+    }
+
+    fn players(&self) -> impl Iterator<Item = &Player> {
+        [self.first.as_ref(), self.second.as_ref()]
+            .into_iter()
+            .flatten()
+    }
+
+    fn player(&self, account_id: &AccountId) -> &Player {
+        self.players()
+            .find(|player| player.account() == account_id)
+            .expect("unknown player")
+    }
+}
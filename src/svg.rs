@@ -0,0 +1,161 @@
+use crate::face::GameCubeFace;
+use crate::game::{Game, GameCube};
+use crate::notation::GameMove;
+use crate::player::{win_position, GamePlayerIndex};
+use crate::position::GameCubePosition;
+
+const CELL: i32 = 60;
+const MARGIN: i32 = 30;
+const SIZE: i32 = 2 * MARGIN + 7 * CELL;
+
+/// What to draw on top of the board position.
+#[derive(Default)]
+pub struct SvgOptions {
+    /// Draw an arrow for the last move.
+    pub last_move: Option<GameMove>,
+    /// Outline cubes the active player may move and mark their legal targets.
+    pub legal_moves: bool,
+}
+
+fn player_colour(player: GamePlayerIndex) -> &'static str {
+    match player {
+        1 => "#2b6cb0",
+        2 => "#c53030",
+        _ => unreachable!("Game Players only have indexes 1 and 2."),
+    }
+}
+
+/// Top-left corner of the square in SVG coordinates.
+fn corner(pos: GameCubePosition) -> (i32, i32) {
+    (
+        MARGIN + (pos.x as i32 - 1) * CELL,
+        MARGIN + (pos.y as i32 - 1) * CELL,
+    )
+}
+
+fn centre(pos: GameCubePosition) -> (i32, i32) {
+    let (x, y) = corner(pos);
+    (x + CELL / 2, y + CELL / 2)
+}
+
+impl Game {
+    /// Render the board as a standalone SVG document.
+    pub fn format_svg(&self, options: &SvgOptions) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{s}\" height=\"{s}\" viewBox=\"0 0 {s} {s}\" font-family=\"sans-serif\">\n",
+            s = SIZE
+        );
+        svg.push_str(
+            "<defs><marker id=\"arrow\" markerWidth=\"8\" markerHeight=\"8\" refX=\"6\" refY=\"4\" orient=\"auto\">\
+             <path d=\"M0,0 L8,4 L0,8 z\" fill=\"#d69e2e\"/></marker></defs>\n",
+        );
+        svg.push_str(&format!(
+            "<rect width=\"{s}\" height=\"{s}\" fill=\"#f7fafc\"/>\n",
+            s = SIZE
+        ));
+
+        for i in 1..=7 {
+            let offset = MARGIN + (i - 1) * CELL + CELL / 2;
+            svg.push_str(&format!(
+                "<text x=\"{offset}\" y=\"{y}\" font-size=\"14\" text-anchor=\"middle\">{col}</text>\n\
+                 <text x=\"{x}\" y=\"{offset}\" font-size=\"14\" text-anchor=\"middle\" dominant-baseline=\"middle\">{i}</text>\n",
+                y = MARGIN - 10,
+                x = MARGIN / 2,
+                col = (b'a' + i as u8 - 1) as char,
+            ));
+        }
+
+        for y in 1..=7 {
+            for x in 1..=7 {
+                svg.push_str(&self.svg_square(GameCubePosition { x, y }));
+            }
+        }
+
+        if options.legal_moves {
+            svg.push_str(&self.svg_legal_moves());
+        }
+
+        for cube in &self.board {
+            svg.push_str(&svg_cube(cube));
+        }
+
+        if let Some(last_move) = &options.last_move {
+            svg.push_str(&svg_arrow(last_move.from, last_move.to));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn svg_square(&self, pos: GameCubePosition) -> String {
+        let (x, y) = corner(pos);
+        let fill = if (pos.x + pos.y) % 2 == 0 {
+            "#e2e8f0"
+        } else {
+            "#edf2f7"
+        };
+        let mut square = format!(
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"{fill}\" stroke=\"#a0aec0\"/>\n"
+        );
+        for player in [1, 2] {
+            if pos == win_position(player) {
+                let (cx, cy) = centre(pos);
+                square.push_str(&format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"{colour}\" fill-opacity=\"0.25\"/>\n\
+                     <text x=\"{cx}\" y=\"{cy}\" font-size=\"28\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{colour}\" fill-opacity=\"0.5\">&#9733;</text>\n",
+                    colour = player_colour(player),
+                ));
+            }
+        }
+        square
+    }
+
+    fn svg_legal_moves(&self) -> String {
+        let moves = self.legal_moves();
+        let mut highlights = String::new();
+
+        let mut selectable: Vec<GameCubePosition> = moves.iter().map(|(from, _)| *from).collect();
+        selectable.dedup();
+        for pos in selectable {
+            let (x, y) = corner(pos);
+            highlights.push_str(&format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\" fill=\"none\" stroke=\"#38a169\" stroke-width=\"4\"/>\n"
+            ));
+        }
+
+        let mut targets: Vec<GameCubePosition> = moves.iter().map(|(_, to)| *to).collect();
+        targets.sort_by_key(|pos| (pos.x, pos.y));
+        targets.dedup();
+        for pos in targets {
+            let (cx, cy) = centre(pos);
+            highlights.push_str(&format!(
+                "<circle cx=\"{cx}\" cy=\"{cy}\" r=\"8\" fill=\"#38a169\" fill-opacity=\"0.6\"/>\n"
+            ));
+        }
+        highlights
+    }
+}
+
+fn svg_cube(cube: &GameCube) -> String {
+    let (x, y) = corner(cube.position);
+    let (cx, cy) = centre(cube.position);
+    let face: GameCubeFace = cube.direction.up.into();
+    let inset = 8;
+    format!(
+        "<rect x=\"{rx}\" y=\"{ry}\" width=\"{w}\" height=\"{w}\" rx=\"8\" fill=\"{colour}\"/>\n\
+         <text x=\"{cx}\" y=\"{cy}\" font-size=\"26\" font-weight=\"bold\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#ffffff\">{glyph}</text>\n",
+        rx = x + inset,
+        ry = y + inset,
+        w = CELL - 2 * inset,
+        colour = player_colour(cube.player),
+        glyph = face.repr_char(),
+    )
+}
+
+fn svg_arrow(from: GameCubePosition, to: GameCubePosition) -> String {
+    let (x1, y1) = centre(from);
+    let (x2, y2) = centre(to);
+    format!(
+        "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"#d69e2e\" stroke-width=\"5\" stroke-opacity=\"0.8\" marker-end=\"url(#arrow)\"/>\n"
+    )
+}
@@ -0,0 +1,102 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{near_bindgen, require, AccountId};
+
+use crate::{currency::Currency, Contract, ContractExt};
+
+/// Stakes accepted in a whitelisted token.
+#[derive(
+    Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeLimits {
+    pub min_stake: U128,
+    pub max_stake: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptedToken {
+    pub token_id: AccountId,
+    #[serde(flatten)]
+    pub limits: StakeLimits,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Accept stakes in `token_id` between `min_stake` and `max_stake`,
+    /// or update the limits of an accepted token. Owner only.
+    pub fn add_token(&mut self, token_id: AccountId, min_stake: U128, max_stake: U128) {
+        self.assert_owner();
+        require!(
+            min_stake.0 <= max_stake.0,
+            "min_stake can't be greater than max_stake"
+        );
+        self.tokens.insert(
+            token_id,
+            StakeLimits {
+                min_stake,
+                max_stake,
+            },
+        );
+    }
+
+    /// Stop accepting stakes in `token_id`. Games already registered are not
+    /// affected. Owner only.
+    pub fn remove_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        require!(
+            self.tokens.remove(&token_id).is_some(),
+            "token is not accepted"
+        );
+    }
+
+    /// Tokens accepted for stakes, with their limits.
+    pub fn accepted_tokens(&self) -> Vec<AcceptedToken> {
+        let mut tokens: Vec<AcceptedToken> = self
+            .tokens
+            .iter()
+            .map(|(token_id, limits)| AcceptedToken {
+                token_id: token_id.clone(),
+                limits: *limits,
+            })
+            .collect();
+        tokens.sort_by(|a, b| a.token_id.cmp(&b.token_id));
+        tokens
+    }
+
+    /// Accept stakes in native NEAR within `limits`, or stop accepting them
+    /// with `null`. Owner only.
+    pub fn set_near_stakes(&mut self, limits: Option<StakeLimits>) {
+        self.assert_owner();
+        if let Some(limits) = limits {
+            require!(
+                limits.min_stake.0 <= limits.max_stake.0,
+                "min_stake can't be greater than max_stake"
+            );
+        }
+        self.near_stakes = limits;
+    }
+
+    /// Limits of stakes in native NEAR, `null` if they are not accepted.
+    pub fn near_stakes(&self) -> Option<StakeLimits> {
+        self.near_stakes
+    }
+
+    /// Check a stake of `amount` in `currency` may be accepted.
+    pub(crate) fn check_stake(&self, currency: &Currency, amount: u128) -> Result<(), String> {
+        let limits = match currency {
+            Currency::Near => self.near_stakes.as_ref(),
+            Currency::Token(token_id) => self.tokens.get(token_id),
+        }
+        .ok_or(format!("{} is not accepted", currency))?;
+        if amount < limits.min_stake.0 || amount > limits.max_stake.0 {
+            return Err(format!(
+                "stake in {} must be between {} and {}",
+                currency, limits.min_stake.0, limits.max_stake.0
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, is_promise_success, log, near_bindgen, require, AccountId, Promise};
+
+use crate::currency::{Balance, Currency};
+use crate::{Contract, ContractExt};
+
+/// House fee taken from a winner's payout unless the owner sets another, 10%.
+pub const DEFAULT_FEE_BPS: u16 = 1_000;
+
+const MAX_FEE_BPS: u16 = 10_000;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Fees {
+    /// Fee taken from each payout, in basis points.
+    pub fee_bps: u16,
+    pub accrued: Vec<Balance>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the fee taken from payouts of the games created from now on,
+    /// in basis points. Owner only.
+    pub fn set_fee(&mut self, fee_bps: u16) {
+        self.assert_owner();
+        require!(
+            fee_bps <= MAX_FEE_BPS,
+            "fee can't be greater than 10000 bps"
+        );
+        self.fee_bps = fee_bps;
+    }
+
+    /// The current fee and the fees accrued in each currency.
+    pub fn fees(&self) -> Fees {
+        let mut accrued: Vec<(&Currency, &u128)> = self.treasury.iter().collect();
+        accrued.sort();
+        Fees {
+            fee_bps: self.fee_bps,
+            accrued: accrued
+                .into_iter()
+                .map(|(currency, amount)| Balance::new(currency, *amount))
+                .collect(),
+        }
+    }
+
+    /// Send `amount` of the fees accrued in `token_id` (`null` for native
+    /// NEAR) to the owner. Owner only.
+    pub fn withdraw_fees(&mut self, token_id: Option<AccountId>, amount: U128) -> Promise {
+        self.assert_owner();
+        let currency = token_id.map_or(Currency::Near, Currency::Token);
+        let accrued = self.treasury.get(&currency).copied().unwrap_or(0);
+        require!(
+            amount.0 > 0 && amount.0 <= accrued,
+            format!("only {} {} of fees accrued", accrued, currency)
+        );
+        self.set_accrued(&currency, accrued - amount.0);
+
+        currency
+            .transfer(self.owner_id.clone(), amount)
+            .then(Self::ext(env::current_account_id()).on_fees_withdrawn(currency, amount))
+    }
+
+    /// Puts the fees back into the treasury if the transfer failed.
+    #[private]
+    pub fn on_fees_withdrawn(&mut self, currency: Currency, amount: U128) {
+        // transfers return no value, so there is no result to deserialize
+        if !is_promise_success() {
+            log!("withdrawal of {} {} failed", amount.0, currency);
+            let accrued = self.treasury.get(&currency).copied().unwrap_or(0);
+            self.set_accrued(&currency, accrued + amount.0);
+        }
+    }
+
+    /// Put the fee taken from a payout into the treasury.
+    pub(crate) fn accrue_fee(&mut self, fee: u128) {
+        let currency = self.currency().clone();
+        let accrued = self.treasury.get(&currency).copied().unwrap_or(0);
+        self.set_accrued(&currency, accrued + fee);
+    }
+
+    fn set_accrued(&mut self, currency: &Currency, amount: u128) {
+        if amount == 0 {
+            self.treasury.remove(currency);
+        } else {
+            self.treasury.insert(currency.clone(), amount);
+        }
+    }
+}
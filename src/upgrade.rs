@@ -0,0 +1,102 @@
+//! Deploying new code over the live contract, keeping its state.
+
+use near_sdk::serde::Serialize;
+use near_sdk::{env, near_bindgen, Gas, Promise};
+
+use crate::external::TGAS;
+use crate::migration::STATE_VERSION;
+use crate::{Contract, ContractExt};
+
+/// Gas for `migrate` after the new code is deployed.
+const MIGRATE_GAS: Gas = Gas(50 * TGAS);
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Version {
+    /// Version of the contract code.
+    pub version: String,
+    /// Version of the layout the state is stored in.
+    pub state_version: u8,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn version(&self) -> Version {
+        Version {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            state_version: STATE_VERSION,
+        }
+    }
+
+    /// Deploy the wasm passed as the raw input of the call, then `migrate`
+    /// the state to it. Owner only.
+    pub fn upgrade(&mut self) -> Promise {
+        self.assert_owner();
+        let code = env::input().expect("pass the new code as the input");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), vec![], 0, MIGRATE_GAS)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+    use near_sdk::{testing_env, AccountId};
+
+    use super::*;
+
+    fn upgrade_from(predecessor: &str) -> Contract {
+        let account = |name: &str| name.parse::<AccountId>().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account("game.near"))
+            .predecessor_account_id(account("game.near"))
+            .build());
+        let mut contract = Contract::new(None, Some(account("owner.near")));
+        let mut context = VMContextBuilder::new()
+            .current_account_id(account("game.near"))
+            .predecessor_account_id(account(predecessor))
+            .build();
+        context.input = b"\0asm".to_vec();
+        testing_env!(context);
+        drop(contract.upgrade());
+        contract
+    }
+
+    #[test]
+    fn test_owner_upgrades() {
+        let contract = upgrade_from("owner.near");
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id.as_str(), "game.near");
+        assert_eq!(
+            receipts[0].actions,
+            vec![
+                VmAction::DeployContract {
+                    code: b"\0asm".to_vec()
+                },
+                VmAction::FunctionCall {
+                    function_name: "migrate".to_string(),
+                    args: vec![],
+                    gas: MIGRATE_GAS,
+                    deposit: 0,
+                },
+            ]
+        );
+
+        // the new code finds the state as the old one left it
+        env::state_write(&contract);
+        let migrated = Contract::migrate();
+        assert_eq!(migrated.owner_id, contract.owner_id);
+        assert_eq!(migrated.version().state_version, STATE_VERSION);
+        assert_eq!(env::storage_read(b"VERSION"), Some(vec![STATE_VERSION]));
+    }
+
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn test_only_owner_upgrades() {
+        upgrade_from("alice.near");
+    }
+}
@@ -1,25 +1,94 @@
-use near_sdk::near_bindgen;
+use near_sdk::json_types::U64;
+use near_sdk::{env, near_bindgen, AccountId};
 
-use crate::{game::Game, player::Player, Contract, ContractExt};
+use crate::{
+    board_view::BoardView,
+    chess_clock::ClockView,
+    currency::Currency,
+    game::{Game, GameId},
+    game_options::Side,
+    notation::GameMove,
+    player::Player,
+    registration::GameStatus,
+    settlement::Settlement,
+    svg::SvgOptions,
+    Contract, ContractExt,
+};
 use near_sdk::serde::{Deserialize, Serialize};
 
 #[near_bindgen]
 impl Contract {
     pub fn status(&self) -> Status {
         Status {
+            game_id: self.game_id,
+            status: self.status,
+            currency: self.currency.clone(),
+            registration_deadline: self.registration_deadline.map(U64),
+            opponent: self.opponent.clone(),
+            side: self.side,
+            creator: self.creator.clone(),
+            fee_bps: self.game_fee_bps,
             first_player: self.first.clone(),
             second_player: self.second.clone(),
         }
     }
 
+    /// What each player and the treasury received in the finished game `game_id`.
+    pub fn settlement(&self, game_id: GameId) -> Option<Settlement> {
+        self.settlements.get(&game_id)
+    }
+
+    /// Players' time left on the contract's clock, in games not streamed
+    /// by roke.to.
+    pub fn clock(&self) -> Option<ClockView> {
+        self.chess_clock
+            .as_ref()
+            .map(|clock| clock.view(env::block_timestamp()))
+    }
+
     pub fn get_game(&self) -> Option<Game> {
         self.game.clone()
     }
+
+    /// The board as a 7x7 grid of cells, with all cube faces and the legal
+    /// moves of the active player, so frontends don't need to know the rules.
+    pub fn board_view(&self) -> Option<BoardView> {
+        self.game.as_ref().map(Game::board_view)
+    }
+
+    /// SVG picture of the board, optionally with an arrow for `last_move`
+    /// (in notation, e.g. "c5-c4") and the active player's legal moves.
+    pub fn board_svg(
+        &self,
+        last_move: Option<String>,
+        legal_moves: Option<bool>,
+    ) -> Option<String> {
+        let last_move = last_move
+            .map(|mv| GameMove::parse(&mv).unwrap_or_else(|| panic!("Cannot parse move '{}'", mv)));
+        let options = SvgOptions {
+            last_move,
+            legal_moves: legal_moves.unwrap_or(false),
+        };
+        self.game.as_ref().map(|game| game.format_svg(&options))
+    }
 }
 
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Status {
+    game_id: GameId,
+    status: GameStatus,
+    /// What the game is played for, once the first player registered.
+    currency: Option<Currency>,
+    /// Nanoseconds timestamp after which the first player's registration expires.
+    registration_deadline: Option<U64>,
+    /// The only account allowed to join the open game, `null` if anyone may.
+    opponent: Option<AccountId>,
+    /// Side the creator asked for: `First`, `Second` or `Random`.
+    side: Side,
+    creator: Option<AccountId>,
+    /// Fee taken from this game's payout, in basis points.
+    fee_bps: Option<u16>,
     first_player: Option<Player>,
     second_player: Option<Player>,
 }
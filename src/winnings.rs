@@ -0,0 +1,120 @@
+//! Winner's payouts: a payout that couldn't be delivered, e.g. because the
+//! winner isn't registered with the token contract, stays claimable.
+
+use std::collections::HashMap;
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, is_promise_success, log, near_bindgen, require, AccountId, Promise};
+
+use crate::currency::{Balance, Currency};
+use crate::events::Event;
+use crate::game::GameId;
+use crate::settlement::PayoutStatus;
+use crate::{Contract, ContractExt};
+
+#[near_bindgen]
+impl Contract {
+    /// Winnings of `account_id` whose payout failed, waiting to be claimed.
+    pub fn claimable(&self, account_id: AccountId) -> Vec<Balance> {
+        let mut winnings: Vec<(Currency, u128)> = self
+            .claimable
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        winnings.sort();
+        winnings
+            .iter()
+            .map(|(currency, amount)| Balance::new(currency, *amount))
+            .collect()
+    }
+
+    /// Send the caller all their claimable winnings.
+    pub fn claim_winnings(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let winnings = self
+            .claimable
+            .remove(&account_id)
+            .filter(|winnings| !winnings.is_empty());
+        require!(winnings.is_some(), "nothing to claim");
+
+        let mut winnings: Vec<(Currency, u128)> = winnings.unwrap().into_iter().collect();
+        winnings.sort();
+        winnings
+            .into_iter()
+            .map(|(currency, amount)| {
+                self.send_winnings(account_id.clone(), currency, amount, None)
+            })
+            .reduce(Promise::and)
+            .unwrap()
+    }
+
+    /// Completes the payout of `game_id`, if any, or keeps the amount
+    /// claimable if it wasn't delivered.
+    #[private]
+    pub fn on_payout(
+        &mut self,
+        account_id: AccountId,
+        currency: Currency,
+        amount: U128,
+        game_id: Option<GameId>,
+    ) {
+        let delivered = is_promise_success();
+        if !delivered {
+            log!(
+                "paying {} {} to {} failed, it can be claimed with claim_winnings",
+                amount.0,
+                currency,
+                account_id
+            );
+            Event::PayoutFailed {
+                account_id: account_id.clone(),
+                token_id: currency.token_id().cloned(),
+                amount,
+            }
+            .emit();
+            self.credit_claimable(&account_id, currency, amount.0);
+        }
+        if let Some(mut settlement) = game_id.and_then(|game_id| self.settlements.get(&game_id)) {
+            settlement.payout_status = if delivered {
+                PayoutStatus::Completed
+            } else {
+                PayoutStatus::Claimable
+            };
+            self.settlements.insert(&settlement.game_id, &settlement);
+        }
+    }
+
+    /// Keep `amount` for `account_id` to claim with `claim_winnings`.
+    pub(crate) fn credit_claimable(
+        &mut self,
+        account_id: &AccountId,
+        currency: Currency,
+        amount: u128,
+    ) {
+        let mut winnings = self.claimable.get(account_id).unwrap_or_default();
+        *winnings.entry(currency).or_default() += amount;
+        self.claimable.insert(account_id, &winnings);
+    }
+
+    /// Transfer `amount` of winnings, of the game `game_id` if it is a payout,
+    /// and check it was delivered in `on_payout`.
+    pub(crate) fn send_winnings(
+        &self,
+        account_id: AccountId,
+        currency: Currency,
+        amount: u128,
+        game_id: Option<GameId>,
+    ) -> Promise {
+        currency.transfer(account_id.clone(), U128(amount)).then(
+            Self::ext(env::current_account_id()).on_payout(
+                account_id,
+                currency,
+                U128(amount),
+                game_id,
+            ),
+        )
+    }
+}
+
+pub(crate) type Winnings = HashMap<Currency, u128>;
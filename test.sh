@@ -6,6 +6,11 @@ game_acc=deblockle-v2.hawthorne.testnet
 streaming_acc=streaming-r-v2.dcversus.testnet
 
 
+# This walkthrough starts from scratch, deleting the deployed game with its
+# games and balances. To update a live contract keeping them, call instead:
+#near call $game_acc upgrade "$(base64 -w0 ./out/main.wasm)" --base64 --accountId $master_acc --gas 300000000000000
+#near view $game_acc version
+
 echo "<------------------------------------------------------------->"
 echo "Delete old contract account."
 echo "<------------------------------------------------------------->"
@@ -35,6 +40,14 @@ near call $game_acc connect_streaming_contract \
     --gas 300000000000000
 
 
+echo "<------------------------------------------------------------->"
+echo "Accept wNEAR stakes."
+echo "<------------------------------------------------------------->"
+near call $game_acc add_token \
+    '{"token_id": "wrap.testnet", "min_stake": "100000000000000000000000", "max_stake": "10000000000000000000000000"}' \
+    --accountId $game_acc
+
+
 echo "<------------------------------------------------------------->"
 echo "Register game contract account."
 echo "<------------------------------------------------------------->"
@@ -59,11 +72,19 @@ near call $game_acc status \
     --accountId $master_acc
 
 
+echo "<------------------------------------------------------------->"
+echo "Cover first player's game storage."
+echo "<------------------------------------------------------------->"
+near call $game_acc storage_deposit '{}' \
+    --accountId $first_player_acc \
+    --deposit 0.022
+
+
 echo "<------------------------------------------------------------->"
 echo "Register first player."
 echo "<------------------------------------------------------------->"
 near call wrap.testnet ft_transfer_call \
-    '{"receiver_id": "deblockle-v2.hawthorne.testnet", "amount": "300000000000000000000000", "msg": "{\"tokens_per_sec\": \"10000\"}"}' \
+    '{"receiver_id": "deblockle-v2.hawthorne.testnet", "amount": "300000000000000000000000", "msg": "{\"CreateGame\": {\"tokens_per_sec\": \"10000\"}}"}' \
     --depositYocto 1 \
     --gas 300000000000000 \
     --accountId $first_player_acc
@@ -95,7 +116,7 @@ echo "<------------------------------------------------------------->"
 echo "Register second player."
 echo "<------------------------------------------------------------->"
 near call wrap.testnet ft_transfer_call \
-    '{"receiver_id": "deblockle-v2.hawthorne.testnet", "amount": "300000000000000000000000", "msg":  "{\"tokens_per_sec\": \"10000\"}"}' \
+    '{"receiver_id": "deblockle-v2.hawthorne.testnet", "amount": "300000000000000000000000", "msg": "{\"JoinGame\": {\"game_id\": 1}}"}' \
     --depositYocto 1 \
     --gas 300000000000000 \
     --accountId $second_player_acc